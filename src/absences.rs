@@ -0,0 +1,87 @@
+use chrono::{Duration, NaiveDate};
+use rusqlite::Connection;
+use std::error::Error;
+use std::result::Result;
+
+use crate::colors::Colors;
+use crate::config::Config;
+use crate::datetime;
+use crate::db;
+
+/*
+# example output:
+
+Thu 24 December 2020  vacation
+Fri 25 December 2020  holiday
+
+*/
+
+fn days(from: NaiveDate, to: NaiveDate) -> Result<Vec<NaiveDate>, Box<dyn Error>> {
+    if to < from {
+        return Err("the last day must not be before the first day".into());
+    }
+    Ok((0..=(to - from).num_days())
+        .map(|i| from + Duration::days(i))
+        .collect())
+}
+
+pub fn list_command(
+    conn: &Connection,
+    year: Option<i32>,
+    config: &Config,
+) -> Result<(), Box<dyn Error>> {
+    let (from, to) = match year {
+        Some(year) => (
+            Some(NaiveDate::from_ymd(year, 1, 1)),
+            Some(NaiveDate::from_ymd(year, 12, 31)),
+        ),
+        None => (None, None),
+    };
+    let absences = db::absence_list(conn, from, to)?;
+    if absences.is_empty() {
+        println!("No absences recorded. Use `punch absences add <day>` to add one.");
+    }
+    for absence in absences {
+        println!(
+            "{day}  {reason}",
+            day = datetime::naivedate_format(absence.day, config).color_heading(&config.colors),
+            reason = absence.reason.unwrap_or_default()
+        );
+    }
+    Ok(())
+}
+
+/// Records the days from `from` to `to` as absences, replacing the reason of days already recorded.
+pub fn add_command(
+    conn: &mut Connection,
+    from: NaiveDate,
+    to: Option<NaiveDate>,
+    reason: Option<&str>,
+) -> Result<(), Box<dyn Error>> {
+    let days = days(from, to.unwrap_or(from))?;
+    let tx = conn.transaction()?;
+    for day in &days {
+        db::absence_set(&tx, *day, reason)?;
+    }
+    tx.commit()?;
+    println!("recorded {} day(s) of absence", days.len());
+    Ok(())
+}
+
+pub fn remove_command(
+    conn: &mut Connection,
+    from: NaiveDate,
+    to: Option<NaiveDate>,
+) -> Result<(), Box<dyn Error>> {
+    let tx = conn.transaction()?;
+    let mut removed = 0;
+    for day in days(from, to.unwrap_or(from))? {
+        removed += db::absence_delete(&tx, day)?;
+    }
+    tx.commit()?;
+    if removed == 0 {
+        return Err("no absence recorded on these days".into());
+    }
+    println!("removed {} day(s) of absence", removed);
+    Ok(())
+}
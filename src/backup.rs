@@ -0,0 +1,145 @@
+use chrono::{Local, NaiveDateTime};
+use log::debug;
+use rusqlite::backup::Progress;
+use rusqlite::{Connection, DatabaseName};
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::result::Result;
+
+use crate::config::Config;
+use crate::migration;
+use crate::profiles;
+use crate::schema;
+
+// profiles get a directory of their own, so that a profile named `punch` does not share the
+// snapshots of the default database
+fn get_backup_dir(db_filename: &Path) -> Result<PathBuf, Box<dyn Error>> {
+    let xdirs = xdg::BaseDirectories::with_prefix("punch")?;
+    Ok(match profiles::profile_name(db_filename)? {
+        Some(name) => xdirs.create_data_directory(Path::new("backups/profiles").join(name))?,
+        None => xdirs.create_data_directory("backups")?,
+    })
+}
+
+// snapshots are named `<stem>.<timestamp>.sqlite`, the timestamp makes lexical order chronological
+const SNAPSHOT_TIMESTAMP_FORMAT: &str = "%Y%m%d-%H%M%S-%3f";
+
+fn snapshot_stem(db_filename: &Path) -> String {
+    db_filename
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| String::from("punch"))
+}
+
+// matches exactly, so that the snapshots of `work.sqlite` and `work-old.sqlite` are told apart
+fn is_snapshot_of(file_name: &str, stem: &str) -> bool {
+    file_name
+        .strip_prefix(stem)
+        .and_then(|rest| rest.strip_prefix('.'))
+        .and_then(|rest| rest.strip_suffix(".sqlite"))
+        .map(|timestamp| {
+            NaiveDateTime::parse_from_str(timestamp, SNAPSHOT_TIMESTAMP_FORMAT).is_ok()
+        })
+        .unwrap_or(false)
+}
+
+fn list_snapshots(backup_dir: &Path, stem: &str) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut snapshots = fs::read_dir(backup_dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            path.file_name()
+                .map(|n| is_snapshot_of(&n.to_string_lossy(), stem))
+                .unwrap_or(false)
+        })
+        .collect::<Vec<PathBuf>>();
+    snapshots.sort();
+    Ok(snapshots)
+}
+
+fn rotate_snapshots(backup_dir: &Path, stem: &str, keep: usize) -> Result<(), Box<dyn Error>> {
+    let snapshots = list_snapshots(backup_dir, stem)?;
+    if snapshots.len() > keep {
+        for old in &snapshots[..snapshots.len() - keep] {
+            debug!("removing old snapshot {}", old.display());
+            fs::remove_file(old)?;
+        }
+    }
+    Ok(())
+}
+
+fn backup_to(conn: &Connection, path: &Path) -> Result<(), Box<dyn Error>> {
+    debug!("writing backup to {}", path.display());
+    conn.backup(DatabaseName::Main, path, None)?;
+    Ok(())
+}
+
+/// Writes a snapshot of the database into the XDG data directory and drops the oldest ones.
+pub fn create_snapshot(
+    conn: &Connection,
+    db_filename: &Path,
+    config: &Config,
+) -> Result<PathBuf, Box<dyn Error>> {
+    let backup_dir = get_backup_dir(db_filename)?;
+    let stem = snapshot_stem(db_filename);
+    let path = backup_dir.join(format!(
+        "{}.{}.sqlite",
+        stem,
+        Local::now().format(SNAPSHOT_TIMESTAMP_FORMAT)
+    ));
+    backup_to(conn, &path)?;
+    rotate_snapshots(&backup_dir, &stem, config.backups.keep)?;
+    Ok(path)
+}
+
+/// Creates a snapshot if there are migrations pending on an already initialized database.
+pub fn snapshot_before_migration(
+    conn: &Connection,
+    db_filename: &Path,
+    config: &Config,
+) -> Result<(), Box<dyn Error>> {
+    let current = migration::current_version(conn)?;
+    if current > 0 && current < migration::latest_version(&schema::migrations()) {
+        let path = create_snapshot(conn, db_filename, config)?;
+        println!(
+            "pending migrations, database backed up to {}",
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+pub fn backup_command(
+    conn: &Connection,
+    db_filename: &Path,
+    path: Option<&str>,
+    config: &Config,
+) -> Result<(), Box<dyn Error>> {
+    let path = match path {
+        Some(p) => {
+            let p = PathBuf::from(p);
+            backup_to(conn, &p)?;
+            p
+        }
+        None => create_snapshot(conn, db_filename, config)?,
+    };
+    println!("database backed up to {}", path.display());
+    Ok(())
+}
+
+pub fn restore_backup_command(
+    conn: &mut Connection,
+    db_filename: &Path,
+    path: &str,
+    config: &Config,
+) -> Result<(), Box<dyn Error>> {
+    if !Path::new(path).is_file() {
+        return Err(format!("backup file {} not found", path).into());
+    }
+    let snapshot = create_snapshot(conn, db_filename, config)?;
+    println!("current database backed up to {}", snapshot.display());
+
+    conn.restore(DatabaseName::Main, path, None::<fn(Progress)>)?;
+    println!("database restored from {}", path);
+    Ok(())
+}
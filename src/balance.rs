@@ -0,0 +1,144 @@
+use chrono::{Datelike, Duration, NaiveDate, TimeZone, Utc};
+use rusqlite::Connection;
+use std::collections::{BTreeMap, HashSet};
+use std::error::Error;
+use std::result::Result;
+
+use crate::colors::Colors;
+use crate::config::Config;
+use crate::datetime;
+use crate::datetime::DisplayTimezone;
+use crate::db;
+use crate::filter::Filter;
+use crate::summarize;
+use crate::summarize::{GroupingMode, PeriodSummaryGrouping};
+
+/*
+# example output:
+
+                                       worked       expected    difference       balance
+Week of Mon 07 September 2020     41h  0m  0s    40h  0m  0s   +1h  0m  0s   +1h  0m  0s
+Week of Mon 14 September 2020     30h  0m  0s    32h  0m  0s   -2h  0m  0s   -1h  0m  0s  1 day off
+
+Balance -1h  0m  0s on Sun 20 September 2020
+
+*/
+
+// time worked per day on all projects, including archived ones
+fn worked_per_day(
+    conn: &Connection,
+    from: Option<NaiveDate>,
+    tz: DisplayTimezone,
+    config: &Config,
+) -> Result<BTreeMap<NaiveDate, Duration>, Box<dyn Error>> {
+    let filter = Filter {
+        // a day of margin as slices are assigned to days in the display timezone
+        from: from.map(|from| Utc.from_utc_date(&from).and_hms(0, 0, 0) - Duration::days(1)),
+        to: None,
+        project: None,
+        archived: true,
+    };
+    let slices = summarize::get_summary_timeslices(conn, &filter, tz)?;
+    Ok(
+        summarize::period_totals(&slices, &GroupingMode::Day, true, config)
+            .into_iter()
+            .filter_map(|(grouping, time)| match grouping {
+                PeriodSummaryGrouping::Day(day) => Some((day, time)),
+                _ => None,
+            })
+            .collect(),
+    )
+}
+
+fn expected_on(day: NaiveDate, config: &Config) -> Duration {
+    let hours = config.schedule.hours_on(day.weekday());
+    Duration::seconds((hours * 3600.0).round() as i64)
+}
+
+/// Prints the over- and undertime per week from `from`, the configured start of the schedule or
+/// the first slice until yesterday, as the current day is not finished yet.
+pub fn balance_command(
+    conn: &Connection,
+    from: Option<NaiveDate>,
+    tz: DisplayTimezone,
+    config: &Config,
+) -> Result<(), Box<dyn Error>> {
+    let from = match (from, &config.schedule.start) {
+        (Some(from), _) => Some(from),
+        (None, Some(start)) => Some(datetime::parse_date(start)?),
+        (None, None) => None,
+    };
+    let worked = worked_per_day(conn, from, tz, config)?;
+    let from = match from.or_else(|| worked.keys().next().copied()) {
+        Some(from) => from,
+        None => {
+            println!("No slices recorded.");
+            return Ok(());
+        }
+    };
+    let until = datetime::today(tz).pred();
+    if until < from {
+        println!(
+            "The balance starts on {}.",
+            datetime::naivedate_format(from, config)
+        );
+        return Ok(());
+    }
+
+    let absences = db::absence_list(conn, Some(from), Some(until))?
+        .into_iter()
+        .map(|absence| absence.day)
+        .collect::<HashSet<_>>();
+
+    // (worked, expected, days off) per week
+    let mut weeks: BTreeMap<NaiveDate, (Duration, Duration, usize)> = BTreeMap::new();
+    let mut day = from;
+    while day <= until {
+        let week = weeks.entry(datetime::week_start(day, config)).or_insert((
+            Duration::zero(),
+            Duration::zero(),
+            0,
+        ));
+        week.0 = week.0 + worked.get(&day).copied().unwrap_or_else(Duration::zero);
+        if absences.contains(&day) {
+            week.2 += 1;
+        } else {
+            week.1 = week.1 + expected_on(day, config);
+        }
+        day = day.succ();
+    }
+
+    println!(
+        "{:<30} {:>14} {:>14} {:>13} {:>13}",
+        "", "worked", "expected", "difference", "balance"
+    );
+    let mut balance = Duration::zero();
+    for (week, (worked, expected, days_off)) in weeks {
+        balance = balance + worked - expected;
+        println!(
+            "{title} {worked:>14} {expected:>14} {difference:>13} {balance:>13}{days_off}",
+            title = format!(
+                "{:<30}",
+                format!("Week of {}", datetime::naivedate_format(week, config))
+            )
+            .color_heading(&config.colors),
+            worked = datetime::format_duration(&worked, config)?.color_duration(&config.colors),
+            expected = datetime::format_duration(&expected, config)?,
+            difference = datetime::format_signed_duration(&(worked - expected), config)?,
+            balance =
+                datetime::format_signed_duration(&balance, config)?.color_duration(&config.colors),
+            days_off = match days_off {
+                0 => String::new(),
+                1 => String::from("  1 day off"),
+                n => format!("  {} days off", n),
+            }
+        );
+    }
+    println!(
+        "\n{} {} on {}",
+        "Balance".color_heading(&config.colors),
+        datetime::format_signed_duration(&balance, config)?.color_duration(&config.colors),
+        datetime::naivedate_format(until, config)
+    );
+    Ok(())
+}
@@ -0,0 +1,169 @@
+use chrono::{Duration, NaiveDate, TimeZone, Utc};
+use rusqlite::Connection;
+use std::error::Error;
+use std::result::Result;
+
+use crate::colors::Colors;
+use crate::config::Config;
+use crate::datetime;
+use crate::datetime::DisplayTimezone;
+use crate::db;
+use crate::filter::Filter;
+use crate::projects;
+use crate::summarize;
+use crate::summarize::GroupingMode;
+
+// quotes a project name for the command printed by `ensure_slice_unlocked`
+fn shell_quote(s: &str) -> String {
+    if s.chars().all(|c| c.is_alphanumeric() || "/-_.".contains(c)) {
+        s.to_string()
+    } else {
+        format!("'{}'", s.replace('\'', "'\\''"))
+    }
+}
+
+/// Fails if the slice has been billed, telling how to unlock it.
+pub fn ensure_slice_unlocked(conn: &Connection, timeslice_id: i64) -> Result<(), Box<dyn Error>> {
+    if !db::timeslice_is_locked(conn, timeslice_id)? {
+        return Ok(());
+    }
+    let timeslice = db::timeslice_get(conn, timeslice_id)?.ok_or("slice not found")?;
+    let project = db::project_get_by_id(conn, timeslice.project_id)?.ok_or("project not found")?;
+    let day = datetime::in_display_timezone(
+        timeslice.started_on,
+        timeslice.utc_offset,
+        DisplayTimezone::Recorded,
+    )
+    .naive_local()
+    .date();
+    Err(format!(
+        "slice {id} is billed and locked, use `punch mark-billed --undo --project {project} --from {day} --to {day}` to unlock it",
+        id = timeslice_id,
+        project = shell_quote(&project.title),
+        day = day.format(datetime::DATE_FORMAT_YMD)
+    )
+    .into())
+}
+
+/// Fails if any slice of the project has been billed.
+pub fn ensure_project_unlocked(
+    conn: &Connection,
+    project: &db::Project,
+) -> Result<(), Box<dyn Error>> {
+    match db::timeslice_count_locked_by_project(conn, project.id)? {
+        0 => Ok(()),
+        locked => Err(format!(
+            "project {} has {} billed and locked slices",
+            project.title, locked
+        )
+        .into()),
+    }
+}
+
+/// Fails if any slice with the tag has been billed.
+pub fn ensure_tag_unlocked(
+    conn: &Connection,
+    tag_id: i64,
+    title: &str,
+) -> Result<(), Box<dyn Error>> {
+    match db::timeslice_count_locked_by_tag(conn, tag_id)? {
+        0 => Ok(()),
+        locked => Err(format!(
+            "tag {} is used by {} billed and locked slices",
+            title, locked
+        )
+        .into()),
+    }
+}
+
+pub fn billable_command(
+    conn: &mut Connection,
+    name: &str,
+    billable: bool,
+    config: &Config,
+) -> Result<(), Box<dyn Error>> {
+    let tx = conn.transaction()?;
+    let project = projects::get_project(&tx, name)?;
+    db::project_set_billable(&tx, project.id, billable)?;
+    tx.commit()?;
+    println!(
+        "new slices of project {} are {}",
+        name.color_project(&config.colors),
+        if billable { "billable" } else { "not billable" }
+    );
+    Ok(())
+}
+
+/// Marks the billable slices with time between `from` and `to` as billed, or unmarks them with
+/// `undo`. A slice counts towards the days it touches in the display timezone, like in
+/// `summarize` and `invoice`.
+#[allow(clippy::too_many_arguments)]
+pub fn mark_billed_command(
+    conn: &mut Connection,
+    project: Option<&str>,
+    from: NaiveDate,
+    to: NaiveDate,
+    invoice_ref: Option<&str>,
+    undo: bool,
+    tz: DisplayTimezone,
+    config: &Config,
+) -> Result<(), Box<dyn Error>> {
+    if from > to {
+        return Err("the range ends before it starts".into());
+    }
+    // a day of margin as slices are assigned to days in the display timezone
+    let filter = Filter {
+        from: Some(Utc.from_utc_date(&from).and_hms(0, 0, 0) - Duration::days(1)),
+        to: Some(Utc.from_utc_date(&to).and_hms(0, 0, 0) + Duration::days(2)),
+        project: project.map(String::from),
+        archived: false,
+    };
+
+    let tx = conn.transaction()?;
+    let slices = summarize::get_summary_timeslices(&tx, &filter, tz)?
+        .into_iter()
+        .filter(|slice| !slice.running)
+        .filter(|slice| {
+            slice
+                .period_pieces(&GroupingMode::Day, true, config)
+                .iter()
+                .any(|(_, day, _)| from <= *day && *day <= to)
+        })
+        .filter(|slice| slice.billable)
+        .collect::<Vec<_>>();
+
+    let (selected, skipped): (Vec<_>, Vec<_>) = slices.into_iter().partition(|slice| {
+        if undo {
+            slice.billed && (invoice_ref.is_none() || slice.invoice_ref.as_deref() == invoice_ref)
+        } else {
+            !slice.billed
+        }
+    });
+    let billed_on = Utc::now();
+    let mut total_time = Duration::zero();
+    for slice in &selected {
+        if undo {
+            db::timeslice_set_billed(&tx, slice.id, None, None)?;
+        } else {
+            db::timeslice_set_billed(&tx, slice.id, Some(billed_on), invoice_ref)?;
+        }
+        total_time = total_time + (slice.stopped_on - slice.started_on);
+    }
+    tx.commit()?;
+
+    println!(
+        "{} {} slices ({}){}",
+        if undo { "unmarked" } else { "marked" },
+        selected.len(),
+        datetime::format_duration(&total_time, config)?.color_duration(&config.colors),
+        match (undo, invoice_ref) {
+            (false, Some(invoice_ref)) => format!(" as billed with invoice {}", invoice_ref),
+            (false, None) => String::from(" as billed"),
+            (true, _) => String::new(),
+        }
+    );
+    if !undo && !skipped.is_empty() {
+        println!("{} slices in the range were already billed", skipped.len());
+    }
+    Ok(())
+}
@@ -0,0 +1,278 @@
+use chrono::Duration;
+use rusqlite::Connection;
+use std::error::Error;
+use std::result::Result;
+
+use crate::colors::Colors;
+use crate::config::Config;
+use crate::datetime;
+use crate::datetime::DisplayTimezone;
+use crate::db;
+use crate::filter::Filter;
+use crate::goals;
+use crate::invoice;
+use crate::projects;
+use crate::rates;
+use crate::summarize;
+
+/*
+# example output:
+
+acme                 [████████████████░░░░]     96h  0m  0s / 120h  0m  0s      80%
+                                                24h  0m  0s remaining
+acme/website         [████████████████████]     5250.00 EUR / 5000.00 EUR     105%
+                                                250.00 EUR over budget
+
+*/
+
+enum Consumption {
+    Hours {
+        used: Duration,
+        budget: Duration,
+    },
+    Money {
+        used: i64,
+        budget: i64,
+        currency: String,
+        /// billable time without a rate in the currency of the budget
+        unpriced: Duration,
+    },
+}
+
+pub struct BudgetUsage {
+    pub project_title: String,
+    consumption: Consumption,
+}
+
+impl BudgetUsage {
+    pub fn percent(&self) -> i64 {
+        match &self.consumption {
+            Consumption::Hours { used, budget } => used.num_seconds() * 100 / budget.num_seconds(),
+            Consumption::Money { used, budget, .. } => used * 100 / budget,
+        }
+    }
+
+    fn format(&self, config: &Config) -> Result<(String, String, String, bool), Box<dyn Error>> {
+        Ok(match &self.consumption {
+            Consumption::Hours { used, budget } => (
+                datetime::format_duration(used, config)?,
+                datetime::format_duration(budget, config)?,
+                datetime::format_duration(
+                    &if used > budget {
+                        *used - *budget
+                    } else {
+                        *budget - *used
+                    },
+                    config,
+                )?
+                .trim()
+                .to_string(),
+                used > budget,
+            ),
+            Consumption::Money {
+                used,
+                budget,
+                currency,
+                ..
+            } => (
+                rates::format_amount(*used, currency),
+                rates::format_amount(*budget, currency),
+                rates::format_amount((budget - used).abs(), currency),
+                used > budget,
+            ),
+        })
+    }
+}
+
+// time is consumed by the slices of the project and all its sub-projects, money only by their
+// billable slices at the most specific rate
+fn get_usage(conn: &Connection, budget: db::Budget) -> Result<BudgetUsage, Box<dyn Error>> {
+    let filter = Filter {
+        from: None,
+        to: None,
+        project: Some(budget.project_title.clone()),
+        archived: false,
+    };
+    let slices = summarize::get_summary_timeslices(conn, &filter, DisplayTimezone::Recorded)?;
+    let consumption = match (budget.minutes, budget.amount, budget.currency) {
+        (Some(minutes), _, _) => Consumption::Hours {
+            used: slices
+                .iter()
+                .fold(Duration::zero(), |sum, slice| sum + slice.duration()),
+            budget: Duration::minutes(minutes),
+        },
+        (None, Some(amount), Some(currency)) => {
+            let rates = invoice::rate_map(conn)?;
+            // cents per hour times seconds, divided once at the end to avoid rounding per slice
+            let mut used = 0;
+            let mut unpriced = Duration::zero();
+            for slice in slices.iter().filter(|slice| slice.billable) {
+                match invoice::line_item_for(slice, &rates) {
+                    (_, Some((hourly_rate, rate_currency))) if rate_currency == currency => {
+                        used += hourly_rate * slice.duration().num_seconds()
+                    }
+                    _ => unpriced = unpriced + slice.duration(),
+                }
+            }
+            Consumption::Money {
+                used: (used + 1800) / 3600,
+                budget: amount,
+                currency,
+                unpriced,
+            }
+        }
+        _ => return Err(format!("invalid budget of {}", budget.project_title).into()),
+    };
+    Ok(BudgetUsage {
+        project_title: budget.project_title,
+        consumption,
+    })
+}
+
+/// The usage of all budgets of the project `project_name` and its sub-projects, or of all budgets.
+pub fn get_usages(
+    conn: &Connection,
+    project_name: Option<&str>,
+) -> Result<Vec<BudgetUsage>, Box<dyn Error>> {
+    db::budget_list(conn)?
+        .into_iter()
+        .filter(|budget| match project_name {
+            Some(name) => projects::project_ancestors(&budget.project_title).contains(&name),
+            None => true,
+        })
+        .map(|budget| get_usage(conn, budget))
+        .collect()
+}
+
+pub fn print_usages(usages: &[BudgetUsage], config: &Config) -> Result<(), Box<dyn Error>> {
+    for usage in usages {
+        let (used, budget, remaining, over) = usage.format(config)?;
+        let (done, goal) = match &usage.consumption {
+            Consumption::Hours { used, budget } => (*used, *budget),
+            // the bar only needs the ratio
+            Consumption::Money { used, budget, .. } => {
+                (Duration::seconds(*used), Duration::seconds(*budget))
+            }
+        };
+        println!(
+            "{title} {bar}  {used:>14} / {budget:<14} {percent:>4}%",
+            title = format!("{:<20}", usage.project_title).color_project(&config.colors),
+            bar = goals::progress_bar(done, goal, config),
+            used = used,
+            budget = budget,
+            percent = usage.percent()
+        );
+        println!(
+            "{:48}{} {}",
+            "",
+            remaining.color_duration(&config.colors),
+            if over { "over budget" } else { "remaining" }
+        );
+        if let Consumption::Money { unpriced, .. } = &usage.consumption {
+            if !unpriced.is_zero() {
+                println!(
+                    "{:48}{} without a rate in this currency",
+                    "",
+                    datetime::format_duration(unpriced, config)?.trim()
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Warns about the budgets of the project and its parents which passed one of the configured
+/// thresholds.
+pub fn warn_thresholds(
+    conn: &Connection,
+    project_name: &str,
+    config: &Config,
+) -> Result<(), Box<dyn Error>> {
+    let ancestors = projects::project_ancestors(project_name);
+    let usages = db::budget_list(conn)?
+        .into_iter()
+        .filter(|budget| ancestors.contains(&budget.project_title.as_str()))
+        .map(|budget| get_usage(conn, budget))
+        .collect::<Result<Vec<_>, _>>()?;
+    for usage in usages {
+        let percent = usage.percent();
+        if let Some(threshold) = config
+            .budgets
+            .warn_at
+            .iter()
+            .filter(|threshold| percent >= **threshold)
+            .max()
+        {
+            println!(
+                "warning: {} has used {}% of its budget (threshold {}%)",
+                usage.project_title.color_project(&config.colors),
+                percent,
+                threshold
+            );
+        }
+    }
+    Ok(())
+}
+
+pub fn list_command(conn: &Connection, config: &Config) -> Result<(), Box<dyn Error>> {
+    let usages = get_usages(conn, None)?;
+    if usages.is_empty() {
+        println!("No budgets set. Use `punch budgets set <project> <hours>` to add one.");
+    }
+    print_usages(&usages, config)
+}
+
+pub fn set_command(
+    conn: &mut Connection,
+    project_name: &str,
+    amount: &str,
+    currency: Option<&str>,
+    config: &Config,
+) -> Result<(), Box<dyn Error>> {
+    let tx = conn.transaction()?;
+    let project = projects::get_project_or_parent(&tx, project_name)?;
+    let description = match currency {
+        Some(currency) => {
+            let amount = rates::parse_amount(amount)?;
+            if amount == 0 {
+                return Err("a budget must be greater than zero".into());
+            }
+            let currency = currency.to_uppercase();
+            db::budget_set(&tx, project.id, None, Some((amount, &currency)))?;
+            rates::format_amount(amount, &currency)
+        }
+        None => {
+            let minutes = goals::parse_hours(amount)?;
+            db::budget_set(&tx, project.id, Some(minutes), None)?;
+            datetime::format_duration(&Duration::minutes(minutes), config)?
+                .trim()
+                .to_string()
+        }
+    };
+    tx.commit()?;
+    println!(
+        "budget of {} set to {}",
+        project_name.color_project(&config.colors),
+        description
+    );
+    Ok(())
+}
+
+pub fn unset_command(
+    conn: &mut Connection,
+    project_name: &str,
+    config: &Config,
+) -> Result<(), Box<dyn Error>> {
+    let tx = conn.transaction()?;
+    let project = projects::get_project(&tx, project_name)?;
+    let deleted = db::budget_delete(&tx, project.id)?;
+    tx.commit()?;
+    if deleted == 0 {
+        return Err("no budget set".into());
+    }
+    println!(
+        "removed budget of {}",
+        project_name.color_project(&config.colors)
+    );
+    Ok(())
+}
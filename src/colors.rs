@@ -1,28 +1,124 @@
-use colored::ColoredString;
-use colored::Colorize;
+use colored::{Color, ColoredString, Colorize};
+use tui::style::{Modifier, Style};
+
+use crate::config::ColorTheme;
+
+// told apart in charts such as `timeline`, projects get them in turn
+const PALETTE: [Color; 12] = [
+    Color::Blue,
+    Color::Green,
+    Color::Yellow,
+    Color::Magenta,
+    Color::Cyan,
+    Color::Red,
+    Color::BrightBlue,
+    Color::BrightGreen,
+    Color::BrightYellow,
+    Color::BrightMagenta,
+    Color::BrightCyan,
+    Color::BrightRed,
+];
+
+/// Colors `s` with the `index`th color of the palette, starting over once all are used.
+pub fn color_palette(s: &str, index: usize) -> ColoredString {
+    s.color(PALETTE[index % PALETTE.len()])
+}
 
 pub trait Colors {
-    fn color_heading(self) -> ColoredString;
-    fn color_project(self) -> ColoredString;
-    fn color_time(self) -> ColoredString;
-    fn color_duration(self) -> ColoredString;
-    fn color_tag(self) -> ColoredString;
+    fn color_heading(self, theme: &ColorTheme) -> ColoredString;
+    fn color_project(self, theme: &ColorTheme) -> ColoredString;
+    fn color_time(self, theme: &ColorTheme) -> ColoredString;
+    fn color_duration(self, theme: &ColorTheme) -> ColoredString;
+    fn color_tag(self, theme: &ColorTheme) -> ColoredString;
+    fn color_id(self, theme: &ColorTheme) -> ColoredString;
+    fn color_note(self, theme: &ColorTheme) -> ColoredString;
+    fn color_warning(self, theme: &ColorTheme) -> ColoredString;
+}
+
+// applies a theme entry such as "bold bright blue", unknown words are ignored
+fn apply_theme(s: &str, theme: &str) -> ColoredString {
+    let mut colored = ColoredString::from(s);
+    let mut color = vec![];
+    for word in theme.split_whitespace() {
+        colored = match word {
+            "bold" => colored.bold(),
+            "italic" => colored.italic(),
+            "underline" => colored.underline(),
+            "dimmed" => colored.dimmed(),
+            _ => {
+                color.push(word);
+                colored
+            }
+        }
+    }
+    match color.join(" ").parse::<Color>() {
+        Ok(c) => colored.color(c),
+        Err(_) => colored,
+    }
+}
+
+/// Turns a theme entry into a style for the full-screen interface of `tui`.
+pub fn theme_style(theme: &str) -> Style {
+    let mut style = Style::default();
+    let mut color = vec![];
+    for word in theme.split_whitespace() {
+        style = match word {
+            "bold" => style.add_modifier(Modifier::BOLD),
+            "italic" => style.add_modifier(Modifier::ITALIC),
+            "underline" => style.add_modifier(Modifier::UNDERLINED),
+            "dimmed" => style.add_modifier(Modifier::DIM),
+            _ => {
+                color.push(word);
+                style
+            }
+        }
+    }
+    let color = match color.join(" ").parse::<Color>() {
+        Ok(Color::Black) => tui::style::Color::Black,
+        Ok(Color::Red) => tui::style::Color::Red,
+        Ok(Color::Green) => tui::style::Color::Green,
+        Ok(Color::Yellow) => tui::style::Color::Yellow,
+        Ok(Color::Blue) => tui::style::Color::Blue,
+        Ok(Color::Magenta) => tui::style::Color::Magenta,
+        Ok(Color::Cyan) => tui::style::Color::Cyan,
+        Ok(Color::White) => tui::style::Color::Gray,
+        Ok(Color::BrightBlack) => tui::style::Color::DarkGray,
+        Ok(Color::BrightRed) => tui::style::Color::LightRed,
+        Ok(Color::BrightGreen) => tui::style::Color::LightGreen,
+        Ok(Color::BrightYellow) => tui::style::Color::LightYellow,
+        Ok(Color::BrightBlue) => tui::style::Color::LightBlue,
+        Ok(Color::BrightMagenta) => tui::style::Color::LightMagenta,
+        Ok(Color::BrightCyan) => tui::style::Color::LightCyan,
+        Ok(Color::BrightWhite) => tui::style::Color::White,
+        Ok(Color::TrueColor { r, g, b }) => tui::style::Color::Rgb(r, g, b),
+        Err(_) => return style,
+    };
+    style.fg(color)
 }
 
-impl<'a> Colors for &'a str {
-    fn color_heading(self) -> ColoredString {
-        self.to_string().bold()
+impl Colors for &str {
+    fn color_heading(self, theme: &ColorTheme) -> ColoredString {
+        apply_theme(self, &theme.heading)
+    }
+    fn color_project(self, theme: &ColorTheme) -> ColoredString {
+        apply_theme(self, &theme.project)
+    }
+    fn color_time(self, theme: &ColorTheme) -> ColoredString {
+        apply_theme(self, &theme.time)
+    }
+    fn color_duration(self, theme: &ColorTheme) -> ColoredString {
+        apply_theme(self, &theme.duration)
     }
-    fn color_project(self) -> ColoredString {
-        self.to_string().purple()
+    fn color_tag(self, theme: &ColorTheme) -> ColoredString {
+        apply_theme(self, &theme.tag)
     }
-    fn color_time(self) -> ColoredString {
-        self.to_string().to_string().green()
+    fn color_id(self, theme: &ColorTheme) -> ColoredString {
+        apply_theme(self, &theme.id)
     }
-    fn color_duration(self) -> ColoredString {
-        self.to_string().to_string().white()
+    fn color_note(self, theme: &ColorTheme) -> ColoredString {
+        apply_theme(self, &theme.note)
     }
-    fn color_tag(self) -> ColoredString {
-        self.to_string().to_string().blue()
+    fn color_warning(self, theme: &ColorTheme) -> ColoredString {
+        apply_theme(self, &theme.warning)
     }
 }
@@ -0,0 +1,464 @@
+use chrono::{FixedOffset, NaiveDate, TimeZone, Weekday};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::result::Result;
+
+use crate::datetime;
+use crate::datetime::{DurationFormat, RoundingMode, RoundingScope};
+use crate::summarize::GroupingMode;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub database: Option<PathBuf>,
+    pub week_start: String,
+    pub date_format: String,
+    pub time_format: String,
+    /// one of `hms`, `decimal`, `hh:mm`, `compact` or `seconds`
+    pub duration_format: String,
+    pub colors: ColorTheme,
+    pub log: LogConfig,
+    pub breaks: BreaksConfig,
+    pub summarize: SummarizeConfig,
+    pub rounding: RoundingConfig,
+    pub budgets: BudgetsConfig,
+    pub backups: BackupsConfig,
+    pub schedule: ScheduleConfig,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            database: None,
+            week_start: String::from("monday"),
+            date_format: String::from("%a %d %B %Y"),
+            time_format: String::from("%H:%M:%S"),
+            duration_format: String::from("hms"),
+            colors: ColorTheme::default(),
+            log: LogConfig::default(),
+            breaks: BreaksConfig::default(),
+            summarize: SummarizeConfig::default(),
+            rounding: RoundingConfig::default(),
+            budgets: BudgetsConfig::default(),
+            backups: BackupsConfig::default(),
+            schedule: ScheduleConfig::default(),
+        }
+    }
+}
+
+/// Each entry is a color name such as `bright blue`, optionally combined with the styles `bold`,
+/// `italic`, `underline` or `dimmed`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ColorTheme {
+    pub heading: String,
+    pub project: String,
+    pub time: String,
+    pub duration: String,
+    pub tag: String,
+    pub id: String,
+    pub note: String,
+    pub warning: String,
+}
+
+impl Default for ColorTheme {
+    fn default() -> Self {
+        ColorTheme {
+            heading: String::from("bold"),
+            project: String::from("purple"),
+            time: String::from("green"),
+            duration: String::from("white"),
+            tag: String::from("blue"),
+            id: String::from("dimmed"),
+            note: String::from("italic"),
+            warning: String::from("yellow"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    /// number of days shown by `log` unless `-a` is given
+    pub range_days: i64,
+    pub split: bool,
+    /// show the breaks between slices and the working hours of each day
+    pub breaks: bool,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        LogConfig {
+            range_days: 7,
+            split: false,
+            breaks: false,
+        }
+    }
+}
+
+/// Breaks required by law or contract, checked by `log --check-breaks`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BreaksConfig {
+    /// shorter gaps between slices do not count as a break
+    pub min_minutes: i64,
+    pub rules: Vec<BreakRule>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct BreakRule {
+    /// the rule applies to days with more working time than this
+    pub after_hours: f64,
+    /// minimum total break
+    pub minutes: i64,
+}
+
+impl Default for BreaksConfig {
+    fn default() -> Self {
+        BreaksConfig {
+            min_minutes: 15,
+            rules: vec![
+                BreakRule {
+                    after_hours: 6.0,
+                    minutes: 30,
+                },
+                BreakRule {
+                    after_hours: 9.0,
+                    minutes: 45,
+                },
+            ],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SummarizeConfig {
+    /// one of `day`, `week`, `month` or `all`
+    pub grouping: String,
+    pub split: bool,
+}
+
+impl Default for SummarizeConfig {
+    fn default() -> Self {
+        SummarizeConfig {
+            grouping: String::from("day"),
+            split: true,
+        }
+    }
+}
+
+/// Default rounding of `summarize` and `invoice`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RoundingConfig {
+    /// granularity in minutes, 0 disables rounding
+    pub minutes: i64,
+    /// one of `up`, `down` or `nearest`
+    pub mode: String,
+    /// `slice` or `day`
+    pub per: String,
+    pub show_raw: bool,
+}
+
+impl Default for RoundingConfig {
+    fn default() -> Self {
+        RoundingConfig {
+            minutes: 0,
+            mode: String::from("nearest"),
+            per: String::from("slice"),
+            show_raw: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BudgetsConfig {
+    /// `start` warns once a budget of the project reaches one of these percentages
+    pub warn_at: Vec<i64>,
+}
+
+impl Default for BudgetsConfig {
+    fn default() -> Self {
+        BudgetsConfig {
+            warn_at: vec![80, 100],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BackupsConfig {
+    /// number of automatic snapshots kept per database
+    pub keep: usize,
+}
+
+impl Default for BackupsConfig {
+    fn default() -> Self {
+        BackupsConfig { keep: 10 }
+    }
+}
+
+/// Expected working hours per weekday, used by `balance`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScheduleConfig {
+    pub monday: f64,
+    pub tuesday: f64,
+    pub wednesday: f64,
+    pub thursday: f64,
+    pub friday: f64,
+    pub saturday: f64,
+    pub sunday: f64,
+    /// first day of the balance as YYYY-MM-DD, defaults to the day of the first slice
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start: Option<String>,
+}
+
+impl Default for ScheduleConfig {
+    fn default() -> Self {
+        ScheduleConfig {
+            monday: 8.0,
+            tuesday: 8.0,
+            wednesday: 8.0,
+            thursday: 8.0,
+            friday: 8.0,
+            saturday: 0.0,
+            sunday: 0.0,
+            start: None,
+        }
+    }
+}
+
+impl ScheduleConfig {
+    pub fn hours_on(&self, weekday: Weekday) -> f64 {
+        match weekday {
+            Weekday::Mon => self.monday,
+            Weekday::Tue => self.tuesday,
+            Weekday::Wed => self.wednesday,
+            Weekday::Thu => self.thursday,
+            Weekday::Fri => self.friday,
+            Weekday::Sat => self.saturday,
+            Weekday::Sun => self.sunday,
+        }
+    }
+}
+
+impl Config {
+    fn validate(&self) -> Result<(), Box<dyn Error>> {
+        self.week_start()?;
+        // chrono only reports unknown specifiers, or time specifiers in a date format, once used
+        let mut formatted = String::new();
+        write!(
+            formatted,
+            "{}",
+            NaiveDate::from_ymd(2020, 9, 12).format(&self.date_format)
+        )
+        .map_err(|_| format!("invalid date_format: {}", self.date_format))?;
+        write!(
+            formatted,
+            "{}",
+            FixedOffset::east(0)
+                .ymd(2020, 9, 12)
+                .and_hms(8, 20, 0)
+                .format(&self.time_format)
+        )
+        .map_err(|_| format!("invalid time_format: {}", self.time_format))?;
+        self.duration_format
+            .parse::<DurationFormat>()
+            .map_err(|e| format!("invalid duration_format: {}", e))?;
+        self.summarize
+            .grouping
+            .parse::<GroupingMode>()
+            .map_err(|e| format!("invalid summarize.grouping: {}", e))?;
+        if self.rounding.minutes < 0 {
+            return Err("invalid rounding.minutes: must not be negative".into());
+        }
+        self.rounding
+            .mode
+            .parse::<RoundingMode>()
+            .map_err(|e| format!("invalid rounding.mode: {}", e))?;
+        self.rounding
+            .per
+            .parse::<RoundingScope>()
+            .map_err(|e| format!("invalid rounding.per: {}", e))?;
+        if self.budgets.warn_at.iter().any(|threshold| *threshold <= 0) {
+            return Err("invalid budgets.warn_at: thresholds must be positive".into());
+        }
+        if self.backups.keep == 0 {
+            return Err("invalid backups.keep: keep at least one snapshot".into());
+        }
+        if self.breaks.min_minutes < 0
+            || self
+                .breaks
+                .rules
+                .iter()
+                .any(|rule| rule.after_hours < 0.0 || rule.minutes < 0)
+        {
+            return Err("invalid breaks: hours and minutes must not be negative".into());
+        }
+        let schedule = &self.schedule;
+        for (weekday, hours) in &[
+            ("monday", schedule.monday),
+            ("tuesday", schedule.tuesday),
+            ("wednesday", schedule.wednesday),
+            ("thursday", schedule.thursday),
+            ("friday", schedule.friday),
+            ("saturday", schedule.saturday),
+            ("sunday", schedule.sunday),
+        ] {
+            if !(0.0..=24.0).contains(hours) {
+                return Err(format!("invalid schedule.{}: must be 0 to 24 hours", weekday).into());
+            }
+        }
+        if let Some(start) = &schedule.start {
+            datetime::parse_date(start).map_err(|e| format!("invalid schedule.start: {}", e))?;
+        }
+        Ok(())
+    }
+
+    pub fn week_start(&self) -> Result<Weekday, Box<dyn Error>> {
+        self.week_start
+            .parse::<Weekday>()
+            .map_err(|_| format!("invalid week_start: {}", self.week_start).into())
+    }
+}
+
+fn read_value(filename: &Path) -> Result<toml::Value, Box<dyn Error>> {
+    if filename.is_file() {
+        Ok(fs::read_to_string(filename)?.parse::<toml::Value>()?)
+    } else {
+        Ok(toml::Value::Table(toml::value::Table::new()))
+    }
+}
+
+pub fn load(filename: &Path) -> Result<Config, Box<dyn Error>> {
+    let config: Config = read_value(filename)?
+        .try_into()
+        .map_err(|e| format!("invalid config file {}: {}", filename.display(), e))?;
+    config.validate()?;
+    Ok(config)
+}
+
+fn effective_value(filename: &Path) -> Result<toml::Value, Box<dyn Error>> {
+    Ok(toml::Value::try_from(load(filename)?)?)
+}
+
+fn lookup<'a>(value: &'a toml::Value, key: &str) -> Option<&'a toml::Value> {
+    key.split('.')
+        .try_fold(value, |value, part| value.as_table()?.get(part))
+}
+
+fn flatten(prefix: &str, value: &toml::Value, out: &mut Vec<(String, toml::Value)>) {
+    match value {
+        toml::Value::Table(table) => {
+            for (key, value) in table {
+                let key = match prefix {
+                    "" => key.to_string(),
+                    _ => format!("{}.{}", prefix, key),
+                };
+                flatten(&key, value, out);
+            }
+        }
+        _ => out.push((prefix.to_string(), value.clone())),
+    }
+}
+
+// values given on the command line take the type of the default value of their key, keys without
+// a default such as `database` are strings
+fn parse_value(key: &str, value: &str) -> Result<toml::Value, Box<dyn Error>> {
+    let defaults = toml::Value::try_from(Config::default())?;
+    let invalid = |expected: &str| format!("invalid value for {}: expected {}", key, expected);
+    Ok(match lookup(&defaults, key) {
+        Some(toml::Value::Boolean(_)) => {
+            toml::Value::Boolean(value.parse().map_err(|_| invalid("true or false"))?)
+        }
+        Some(toml::Value::Integer(_)) => {
+            toml::Value::Integer(value.parse().map_err(|_| invalid("a whole number"))?)
+        }
+        Some(toml::Value::Float(_)) => {
+            toml::Value::Float(value.parse().map_err(|_| invalid("a number"))?)
+        }
+        Some(toml::Value::Array(_)) => format!("value = {}", value)
+            .parse::<toml::Value>()
+            .ok()
+            .and_then(|table| table.get("value").cloned())
+            .filter(toml::Value::is_array)
+            .ok_or_else(|| invalid("an array such as [80, 100]"))?,
+        Some(toml::Value::Table(_)) => return Err(format!("{} is a section", key).into()),
+        _ => toml::Value::String(value.to_string()),
+    })
+}
+
+// the display of `toml::Value` writes tables within arrays as sections, keep them on one line
+fn inline(value: &toml::Value) -> String {
+    match value {
+        toml::Value::Array(values) => format!(
+            "[{}]",
+            values.iter().map(inline).collect::<Vec<_>>().join(", ")
+        ),
+        toml::Value::Table(table) => format!(
+            "{{ {} }}",
+            table
+                .iter()
+                .map(|(key, value)| format!("{} = {}", key, inline(value)))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        _ => value.to_string(),
+    }
+}
+
+pub fn get_command(filename: &Path, key: &str) -> Result<(), Box<dyn Error>> {
+    let value = effective_value(filename)?;
+    match lookup(&value, key) {
+        Some(toml::Value::String(s)) => println!("{}", s),
+        Some(v) => println!("{}", inline(v)),
+        None => return Err(format!("unknown config key: {}", key).into()),
+    }
+    Ok(())
+}
+
+pub fn set_command(filename: &Path, key: &str, value: &str) -> Result<(), Box<dyn Error>> {
+    let mut file_value = read_value(filename)?;
+
+    let mut parts = key.split('.').collect::<Vec<&str>>();
+    let last = parts.pop().ok_or("empty config key")?;
+    let mut table = file_value.as_table_mut().ok_or("invalid config file")?;
+    for part in parts {
+        table = table
+            .entry(part.to_string())
+            .or_insert_with(|| toml::Value::Table(toml::value::Table::new()))
+            .as_table_mut()
+            .ok_or(format!("{} is not a section", part))?;
+    }
+    table.insert(last.to_string(), parse_value(key, value)?);
+
+    // make sure the new file is still a valid config before writing it
+    let config: Config = file_value
+        .clone()
+        .try_into()
+        .map_err(|e| format!("invalid value for {}: {}", key, e))?;
+    config.validate()?;
+
+    if let Some(dir) = filename.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(filename, toml::to_string_pretty(&file_value)?)?;
+    Ok(())
+}
+
+pub fn list_command(filename: &Path) -> Result<(), Box<dyn Error>> {
+    let mut entries = vec![];
+    flatten("", &effective_value(filename)?, &mut entries);
+    for (key, value) in entries {
+        println!("{} = {}", key, inline(&value));
+    }
+    Ok(())
+}
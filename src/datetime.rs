@@ -1,10 +1,16 @@
-use chrono::{DateTime, Duration, FixedOffset, Local, NaiveDate, TimeZone, Utc};
+use chrono::{
+    DateTime, Datelike, Duration, FixedOffset, Local, NaiveDate, Offset, TimeZone, Utc, Weekday,
+};
+use chrono_tz::Tz;
 
+use std::collections::HashMap;
 use std::error::Error;
 use std::fmt::Display;
 use std::fmt::Write;
+use std::str::FromStr;
+
+use crate::config::Config;
 
-const DATE_FORMAT: &str = "%a %d %B %Y";
 pub const DATE_FORMAT_YMD: &str = "%F";
 
 pub fn timestamp_1970() -> DateTime<Utc> {
@@ -15,28 +21,301 @@ pub fn from_rfc3339_string(as_string: &str) -> DateTime<FixedOffset> {
     DateTime::parse_from_rfc3339(as_string).unwrap()
 }
 
-pub fn naivedate_from_string(as_string: &str) -> NaiveDate {
-    NaiveDate::parse_from_str(as_string, "%Y-%m-%d").unwrap()
+/// Parses a date given on the command line as `YYYY-MM-DD`.
+pub fn parse_date(date: &str) -> Result<NaiveDate, Box<dyn Error>> {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map_err(|_| format!("invalid date {}, use YYYY-MM-DD", date).into())
+}
+
+/// Parses a month such as `2020-09` into its first day.
+pub fn parse_month(month: &str) -> Result<NaiveDate, Box<dyn Error>> {
+    parse_date(&format!("{}-01", month))
+        .map_err(|_| format!("invalid month {}, use YYYY-MM", month).into())
+}
+
+/// Parses a year such as `2020`.
+pub fn parse_year(year: &str) -> Result<i32, Box<dyn Error>> {
+    match year.parse::<i32>() {
+        Ok(y) if (1000..=9999).contains(&y) => Ok(y),
+        _ => Err(format!("invalid year {}, use e.g. 2020", year).into()),
+    }
+}
+
+/// Returns the first day of the month following the month of `date`.
+pub fn next_month(date: NaiveDate) -> NaiveDate {
+    match date.month() {
+        12 => NaiveDate::from_ymd(date.year() + 1, 1, 1),
+        m => NaiveDate::from_ymd(date.year(), m + 1, 1),
+    }
 }
 
-pub fn naivedate_format(d: NaiveDate) -> String {
-    format!("{}", d.format(DATE_FORMAT))
+pub fn naivedate_format(d: NaiveDate, config: &Config) -> String {
+    format!("{}", d.format(&config.date_format))
 }
 
 pub fn as_local<T: TimeZone>(dt: DateTime<T>) -> DateTime<Local> {
     dt.with_timezone(&Local)
 }
 
-#[allow(dead_code)]
 pub fn as_utc<T: TimeZone>(dt: DateTime<T>) -> DateTime<Utc> {
     dt.with_timezone(&Utc)
 }
 
-pub fn datetime_as_time_string<T: TimeZone>(dt: &DateTime<T>) -> String
+/// Timezone used to display slices and to assign them to calendar days.
+#[derive(Debug, Clone, Copy)]
+pub enum DisplayTimezone {
+    /// The offset recorded with each slice, the system timezone for slices recorded without one.
+    Recorded,
+    Named(Tz),
+}
+
+pub fn parse_display_timezone(name: &str) -> Result<DisplayTimezone, Box<dyn Error>> {
+    name.parse::<Tz>()
+        .map(DisplayTimezone::Named)
+        .map_err(|e| e.into())
+}
+
+pub fn local_utc_offset() -> i32 {
+    Local::now().offset().local_minus_utc()
+}
+
+pub fn in_display_timezone(
+    dt: DateTime<Utc>,
+    utc_offset: Option<i32>,
+    tz: DisplayTimezone,
+) -> DateTime<FixedOffset> {
+    let offset = match tz {
+        DisplayTimezone::Recorded => match utc_offset {
+            Some(seconds) => FixedOffset::east(seconds),
+            None => *dt.with_timezone(&Local).offset(),
+        },
+        DisplayTimezone::Named(tz) => dt.with_timezone(&tz).offset().fix(),
+    };
+    dt.with_timezone(&offset)
+}
+
+/// Returns the current date in the display timezone.
+pub fn today(tz: DisplayTimezone) -> NaiveDate {
+    in_display_timezone(Utc::now(), None, tz)
+        .naive_local()
+        .date()
+}
+
+/// Returns the first day of the week containing `date`, according to the configured week start.
+pub fn week_start(date: NaiveDate, config: &Config) -> NaiveDate {
+    let first = config.week_start().unwrap_or(Weekday::Mon);
+    let offset = (date.weekday().num_days_from_monday() + 7 - first.num_days_from_monday()) % 7;
+    date - Duration::days(offset as i64)
+}
+
+/// Splits the interval between `start` and `stop` at every midnight in between.
+pub fn split_at_midnight(
+    start: DateTime<FixedOffset>,
+    stop: DateTime<FixedOffset>,
+) -> Vec<(DateTime<FixedOffset>, DateTime<FixedOffset>)> {
+    let mut pieces = vec![];
+    let mut piece_start = start;
+    loop {
+        let next_midnight = piece_start.date().succ().and_hms(0, 0, 0);
+        if next_midnight >= stop {
+            pieces.push((piece_start, stop));
+            return pieces;
+        }
+        pieces.push((piece_start, next_midnight));
+        piece_start = next_midnight;
+    }
+}
+
+pub fn datetime_as_time_string<T: TimeZone>(dt: &DateTime<T>, config: &Config) -> String
 where
     T::Offset: Display,
 {
-    format!("{}", dt.format("%H:%M:%S"))
+    format!("{}", dt.format(&config.time_format))
+}
+
+pub enum RoundingMode {
+    Up,
+    Down,
+    Nearest,
+}
+
+impl FromStr for RoundingMode {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "up" => Ok(RoundingMode::Up),
+            "down" => Ok(RoundingMode::Down),
+            "nearest" => Ok(RoundingMode::Nearest),
+            _ => Err(format!("unknown rounding mode: {}", name)),
+        }
+    }
+}
+
+/// Selects whether every slice is rounded on its own or the time of each day.
+pub enum RoundingScope {
+    Slice,
+    Day,
+}
+
+impl FromStr for RoundingScope {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "slice" => Ok(RoundingScope::Slice),
+            "day" => Ok(RoundingScope::Day),
+            _ => Err(format!("unknown rounding scope: {}", name)),
+        }
+    }
+}
+
+pub struct Rounding {
+    pub mode: RoundingMode,
+    pub granularity: Duration,
+    pub scope: RoundingScope,
+}
+
+/// Rounds a duration to a multiple of `granularity`, e.g. 15 minutes.
+pub fn round_duration(duration: Duration, mode: &RoundingMode, granularity: Duration) -> Duration {
+    let step = granularity.num_seconds();
+    if step <= 0 {
+        return duration;
+    }
+    let seconds = duration.num_seconds();
+    let steps = match mode {
+        RoundingMode::Up => (seconds + step - 1).div_euclid(step),
+        RoundingMode::Down => seconds.div_euclid(step),
+        RoundingMode::Nearest => (seconds + step / 2).div_euclid(step),
+    };
+    Duration::seconds(steps * step)
+}
+
+/// Sums up durations, keeping the raw total along with the total rounded per slice or per day.
+#[derive(Debug, Clone)]
+pub struct RoundedSum {
+    pub raw: Duration,
+    rounded_slices: Duration,
+    days: HashMap<NaiveDate, Duration>,
+}
+
+impl RoundedSum {
+    pub fn new() -> RoundedSum {
+        RoundedSum {
+            raw: Duration::zero(),
+            rounded_slices: Duration::zero(),
+            days: HashMap::new(),
+        }
+    }
+
+    /// Adds the time of a slice, or the part of it, spent on `day`.
+    pub fn add(&mut self, day: NaiveDate, duration: Duration, rounding: Option<&Rounding>) {
+        self.raw = self.raw + duration;
+        match rounding {
+            Some(Rounding {
+                mode,
+                granularity,
+                scope: RoundingScope::Slice,
+            }) => {
+                self.rounded_slices =
+                    self.rounded_slices + round_duration(duration, mode, *granularity)
+            }
+            Some(Rounding {
+                scope: RoundingScope::Day,
+                ..
+            }) => {
+                let day_time = self.days.entry(day).or_insert_with(Duration::zero);
+                *day_time = *day_time + duration;
+            }
+            None => {}
+        }
+    }
+
+    /// Returns the total rounded as given to `add`, or the raw total without rounding.
+    pub fn rounded(&self, rounding: Option<&Rounding>) -> Duration {
+        match rounding {
+            Some(Rounding {
+                scope: RoundingScope::Slice,
+                ..
+            }) => self.rounded_slices,
+            Some(Rounding {
+                mode,
+                granularity,
+                scope: RoundingScope::Day,
+            }) => self
+                .days
+                .values()
+                .fold(Duration::zero(), |total, day_time| {
+                    total + round_duration(*day_time, mode, *granularity)
+                }),
+            None => self.raw,
+        }
+    }
+}
+
+pub enum DurationFormat {
+    /// `3h 45m 12s`
+    Hms,
+    /// `3.75`
+    Decimal,
+    /// `03:45`
+    HhMm,
+    /// `3h45`
+    Compact,
+    /// `13512`
+    Seconds,
+}
+
+impl FromStr for DurationFormat {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "hms" => Ok(DurationFormat::Hms),
+            "decimal" => Ok(DurationFormat::Decimal),
+            "hh:mm" => Ok(DurationFormat::HhMm),
+            "compact" => Ok(DurationFormat::Compact),
+            "seconds" => Ok(DurationFormat::Seconds),
+            _ => Err(format!("unknown duration format: {}", name)),
+        }
+    }
+}
+
+/// Formats a duration as selected by the duration_format setting.
+pub fn format_duration(duration: &Duration, config: &Config) -> Result<String, Box<dyn Error>> {
+    let format = config.duration_format.parse::<DurationFormat>()?;
+    if *duration < Duration::zero() {
+        return Ok(format!("-{}", format_duration(&-*duration, config)?));
+    }
+    let minutes = duration.num_minutes() % 60;
+    Ok(match format {
+        DurationFormat::Hms => duration_as_hms_string(duration)?,
+        DurationFormat::Decimal => format!("{:.2}", duration.num_seconds() as f64 / 3600.0),
+        DurationFormat::HhMm => format!("{:02}:{:02}", duration.num_hours(), minutes),
+        DurationFormat::Compact => match (duration.num_hours(), minutes) {
+            (0, m) => format!("{}m", m),
+            (h, 0) => format!("{}h", h),
+            (h, m) => format!("{}h{:02}", h, m),
+        },
+        DurationFormat::Seconds => duration.num_seconds().to_string(),
+    })
+}
+
+/// Formats a difference such as an over- or undertime with an explicit sign, e.g. `+2h  0m  0s`.
+pub fn format_signed_duration(
+    duration: &Duration,
+    config: &Config,
+) -> Result<String, Box<dyn Error>> {
+    let (sign, magnitude) = if *duration < Duration::zero() {
+        ("-", -*duration)
+    } else {
+        ("+", *duration)
+    };
+    Ok(format!(
+        "{}{}",
+        sign,
+        format_duration(&magnitude, config)?.trim_start()
+    ))
 }
 
 pub fn duration_as_hms_string(duration: &Duration) -> Result<String, Box<dyn Error>> {
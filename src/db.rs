@@ -1,5 +1,5 @@
-use chrono::{DateTime, Utc};
-use rusqlite::{named_params, params, Connection, OptionalExtension, Result};
+use chrono::{DateTime, NaiveDate, Utc};
+use rusqlite::{named_params, params, Connection, OptionalExtension, Result, NO_PARAMS};
 
 // project
 /////////////////////////////
@@ -7,17 +7,22 @@ use rusqlite::{named_params, params, Connection, OptionalExtension, Result};
 pub struct Project {
     pub id: i64,
     pub title: String,
+    pub archived: bool,
+    /// default for new slices of the project
+    pub billable: bool,
 }
 
 pub fn project_get_by_name(conn: &Connection, name: &str) -> Result<Option<Project>> {
     let res = conn
         .query_row(
-            "SELECT project_id, title FROM project WHERE title = ?",
+            "SELECT project_id, title, archived, billable FROM project WHERE title = ?",
             &[name],
             |row| {
                 Ok(Project {
                     id: row.get(0)?,
                     title: row.get(1)?,
+                    archived: row.get(2)?,
+                    billable: row.get(3)?,
                 })
             },
         )
@@ -26,11 +31,78 @@ pub fn project_get_by_name(conn: &Connection, name: &str) -> Result<Option<Proje
     Ok(res)
 }
 
+pub fn project_get_by_id(conn: &Connection, project_id: i64) -> Result<Option<Project>> {
+    conn.query_row(
+        "SELECT project_id, title, archived, billable FROM project WHERE project_id = ?1",
+        params![project_id],
+        |row| {
+            Ok(Project {
+                id: row.get(0)?,
+                title: row.get(1)?,
+                archived: row.get(2)?,
+                billable: row.get(3)?,
+            })
+        },
+    )
+    .optional()
+}
+
+/// Lists the sub-projects of a project at all levels, parents before their children.
+pub fn project_list_descendants(conn: &Connection, title: &str) -> Result<Vec<Project>> {
+    let mut stmt = conn.prepare(
+        "
+        SELECT project_id, title, archived, billable
+        FROM project
+        WHERE substr(title, 1, length(?1) + 1) = ?1 || '/'
+        ORDER BY title
+        ",
+    )?;
+    let projects = stmt
+        .query_map(params![title], |row| {
+            Ok(Project {
+                id: row.get(0)?,
+                title: row.get(1)?,
+                archived: row.get(2)?,
+                billable: row.get(3)?,
+            })
+        })?
+        .collect();
+    projects
+}
+
 pub fn project_create(conn: &Connection, title: &str) -> Result<i64> {
     conn.execute("INSERT INTO project (title) VALUES (?1)", params![title])?;
     Ok(conn.last_insert_rowid())
 }
 
+pub fn project_rename(conn: &Connection, project_id: i64, title: &str) -> Result<usize> {
+    conn.execute(
+        "UPDATE project SET title = ?1 WHERE project_id = ?2",
+        params![title, project_id],
+    )
+}
+
+pub fn project_set_archived(conn: &Connection, project_id: i64, archived: bool) -> Result<usize> {
+    conn.execute(
+        "UPDATE project SET archived = ?1 WHERE project_id = ?2",
+        params![archived, project_id],
+    )
+}
+
+pub fn project_set_billable(conn: &Connection, project_id: i64, billable: bool) -> Result<usize> {
+    conn.execute(
+        "UPDATE project SET billable = ?1 WHERE project_id = ?2",
+        params![billable, project_id],
+    )
+}
+
+pub fn project_delete(conn: &Connection, project_id: i64) -> Result<usize> {
+    conn.execute(
+        "DELETE FROM project WHERE project_id = ?1",
+        params![project_id],
+    )
+}
+
 // timeslice
 /////////////////////////////
 pub struct Timeslice {
@@ -38,6 +110,9 @@ pub struct Timeslice {
     pub project_id: i64,
     pub started_on: DateTime<Utc>,
     pub stopped_on: Option<DateTime<Utc>>,
+    pub utc_offset: Option<i32>,
+    pub note: Option<String>,
+    pub billable: bool,
 }
 pub fn timeslice_create(conn: &Connection, timeslice: Timeslice) -> Result<i64> {
     let mut params: Vec<&dyn rusqlite::ToSql> =
@@ -48,33 +123,261 @@ pub fn timeslice_create(conn: &Connection, timeslice: Timeslice) -> Result<i64>
     } else {
         params.push(&rusqlite::types::Null);
     }
+    params.push(&timeslice.utc_offset);
+    params.push(&timeslice.note);
+    params.push(&timeslice.billable);
 
     conn.execute(
-        "INSERT INTO timeslice (project_id, started_on, stopped_on) VALUES (?1, ?2, ?3);",
+        "INSERT INTO timeslice (project_id, started_on, stopped_on, utc_offset, note, billable) VALUES (?1, ?2, ?3, ?4, ?5, ?6);",
         params,
     )?;
     Ok(conn.last_insert_rowid())
 }
 
+pub fn timeslice_get(conn: &Connection, timeslice_id: i64) -> Result<Option<Timeslice>> {
+    conn.query_row(
+        "
+        SELECT timeslice_id, project_id, started_on, stopped_on, utc_offset, note, billable
+        FROM timeslice
+        WHERE timeslice_id = ?1
+        ",
+        params![timeslice_id],
+        |row| {
+            Ok(Timeslice {
+                id: row.get(0)?,
+                project_id: row.get(1)?,
+                started_on: row.get(2)?,
+                stopped_on: row.get(3)?,
+                utc_offset: row.get(4)?,
+                note: row.get(5)?,
+                billable: row.get(6)?,
+            })
+        },
+    )
+    .optional()
+}
+
+/// Saves all fields of `timeslice` to the slice with its id.
+pub fn timeslice_update(conn: &Connection, timeslice: &Timeslice) -> Result<usize> {
+    conn.execute(
+        "
+        UPDATE timeslice
+        SET project_id = ?1, started_on = ?2, stopped_on = ?3, utc_offset = ?4, note = ?5, billable = ?6
+        WHERE timeslice_id = ?7
+        ",
+        params![
+            timeslice.project_id,
+            timeslice.started_on,
+            timeslice.stopped_on,
+            timeslice.utc_offset,
+            timeslice.note,
+            timeslice.billable,
+            timeslice.id
+        ],
+    )
+}
+
+/// Deletes a slice together with its tag links.
+pub fn timeslice_delete(conn: &Connection, timeslice_id: i64) -> Result<usize> {
+    timeslice_tag_clear(conn, timeslice_id)?;
+    conn.execute(
+        "DELETE FROM timeslice WHERE timeslice_id = ?1",
+        params![timeslice_id],
+    )
+}
+
+pub fn timeslice_move_to_project(
+    conn: &Connection,
+    from_project_id: i64,
+    to_project_id: i64,
+) -> Result<usize> {
+    conn.execute(
+        "UPDATE timeslice SET project_id = ?1 WHERE project_id = ?2",
+        params![to_project_id, from_project_id],
+    )
+}
+
+pub fn timeslice_get_note(conn: &Connection, timeslice_id: i64) -> Result<Option<Option<String>>> {
+    conn.query_row(
+        "SELECT note FROM timeslice WHERE timeslice_id = ?1",
+        params![timeslice_id],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+pub fn timeslice_set_note(
+    conn: &Connection,
+    timeslice_id: i64,
+    note: Option<&str>,
+) -> Result<usize> {
+    conn.execute(
+        "UPDATE timeslice SET note = ?1 WHERE timeslice_id = ?2",
+        params![note, timeslice_id],
+    )
+}
+
+/// Adds a line to the note of a timeslice.
+pub fn timeslice_append_note(conn: &Connection, timeslice_id: i64, note: &str) -> Result<usize> {
+    conn.execute(
+        "UPDATE timeslice SET note = COALESCE(note || char(10), '') || ?1 WHERE timeslice_id = ?2",
+        params![note, timeslice_id],
+    )
+}
+
+/// Marks a slice as billed, locking it against changes, or unlocks it again when `billed_on` is
+/// `None`.
+pub fn timeslice_set_billed(
+    conn: &Connection,
+    timeslice_id: i64,
+    billed_on: Option<DateTime<Utc>>,
+    invoice_ref: Option<&str>,
+) -> Result<usize> {
+    conn.execute(
+        "UPDATE timeslice SET billed_on = ?1, invoice_ref = ?2 WHERE timeslice_id = ?3",
+        params![billed_on, invoice_ref, timeslice_id],
+    )
+}
+
+pub fn timeslice_is_locked(conn: &Connection, timeslice_id: i64) -> Result<bool> {
+    conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM timeslice WHERE timeslice_id = ?1 AND billed_on IS NOT NULL)",
+        params![timeslice_id],
+        |row| row.get(0),
+    )
+}
+
+pub fn timeslice_count_locked_by_project(conn: &Connection, project_id: i64) -> Result<i64> {
+    conn.query_row(
+        "SELECT count(*) FROM timeslice WHERE project_id = ?1 AND billed_on IS NOT NULL",
+        params![project_id],
+        |row| row.get(0),
+    )
+}
+
+pub fn timeslice_count_locked_by_tag(conn: &Connection, tag_id: i64) -> Result<i64> {
+    conn.query_row(
+        "
+        SELECT count(*)
+        FROM timeslice
+        JOIN timeslice_tag USING(timeslice_id)
+        WHERE tag_id = ?1 AND billed_on IS NOT NULL
+        ",
+        params![tag_id],
+        |row| row.get(0),
+    )
+}
+
+pub fn timeslice_list_ids_by_tag_and_project(
+    conn: &Connection,
+    tag_id: i64,
+    project_id: i64,
+) -> Result<Vec<i64>> {
+    let mut stmt = conn.prepare(
+        "
+        SELECT timeslice_id
+        FROM timeslice
+        JOIN timeslice_tag USING(timeslice_id)
+        WHERE tag_id = ?1 AND project_id = ?2
+        ",
+    )?;
+    let ids = stmt
+        .query_map(params![tag_id, project_id], |row| row.get(0))?
+        .collect();
+    ids
+}
+
 // tag
 /////////////////////////////
+#[derive(Debug)]
+pub struct Tag {
+    pub id: i64,
+    pub title: String,
+}
+
+pub fn tag_list_by_project_id(conn: &Connection, project_id: i64) -> Result<Vec<Tag>> {
+    let mut stmt = conn.prepare("SELECT tag_id, title FROM tag WHERE project_id = ?1")?;
+    let tags = stmt
+        .query_map(params![project_id], |row| {
+            Ok(Tag {
+                id: row.get(0)?,
+                title: row.get(1)?,
+            })
+        })?
+        .collect();
+    tags
+}
+
+/// Lists the tags of all projects with the given title, global tags excluded.
+pub fn tag_list_project_tags_by_name(conn: &Connection, title: &str) -> Result<Vec<Tag>> {
+    let mut stmt =
+        conn.prepare("SELECT tag_id, title FROM tag WHERE title = ?1 AND project_id IS NOT NULL")?;
+    let tags = stmt
+        .query_map(params![title], |row| {
+            Ok(Tag {
+                id: row.get(0)?,
+                title: row.get(1)?,
+            })
+        })?
+        .collect();
+    tags
+}
+
+pub fn tag_rename(conn: &Connection, tag_id: i64, title: &str) -> Result<usize> {
+    conn.execute(
+        "UPDATE tag SET title = ?1 WHERE tag_id = ?2",
+        params![title, tag_id],
+    )
+}
+
+pub fn tag_move_to_project(conn: &Connection, tag_id: i64, project_id: i64) -> Result<usize> {
+    conn.execute(
+        "UPDATE tag SET project_id = ?1 WHERE tag_id = ?2",
+        params![project_id, tag_id],
+    )
+}
+
+pub fn tag_delete(conn: &Connection, tag_id: i64) -> Result<usize> {
+    conn.execute("DELETE FROM tag WHERE tag_id = ?1", params![tag_id])
+}
+
 pub fn tag_get_id_by_name_and_project_id(
     conn: &Connection,
     title: &str,
     project_id: i64,
 ) -> Result<Option<i64>> {
-    Ok(conn
-        .query_row_named(
-            "SELECT tag_id FROM tag WHERE title = :title AND project_id = :project_id",
-            named_params! {":title": title, ":project_id": project_id},
-            |row| row.get(0),
-        )
-        .optional()?)
+    conn.query_row_named(
+        "SELECT tag_id FROM tag WHERE title = :title AND project_id = :project_id",
+        named_params! {":title": title, ":project_id": project_id},
+        |row| row.get(0),
+    )
+    .optional()
 }
 
+/// Looks up a tag usable in a project: a tag of the project itself, otherwise a global tag.
+/// Without a project only global tags are considered.
+pub fn tag_get_id_by_name_for_project(
+    conn: &Connection,
+    title: &str,
+    project_id: Option<i64>,
+) -> Result<Option<i64>> {
+    conn.query_row_named(
+        "
+        SELECT tag_id FROM tag
+        WHERE title = :title AND (project_id = :project_id OR project_id IS NULL)
+        ORDER BY project_id IS NULL
+        LIMIT 1
+        ",
+        named_params! {":title": title, ":project_id": project_id},
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+/// A tag with `project_id` set to `None` is global and shared by all projects.
 pub struct TagCreate {
     pub title: String,
-    pub project_id: i64,
+    pub project_id: Option<i64>,
 }
 pub fn tag_create(conn: &Connection, tag: TagCreate) -> Result<i64> {
     conn.execute_named(
@@ -86,7 +389,7 @@ pub fn tag_create(conn: &Connection, tag: TagCreate) -> Result<i64> {
 
 pub fn tag_get_id_or_create(conn: &Connection, tag: TagCreate) -> Result<i64> {
     Ok(
-        match tag_get_id_by_name_and_project_id(conn, &tag.title, tag.project_id)? {
+        match tag_get_id_by_name_for_project(conn, &tag.title, tag.project_id)? {
             None => tag_create(conn, tag)?,
             Some(t) => t,
         },
@@ -107,3 +410,300 @@ pub fn tag_assign_to_timeslice(
     )?;
     Ok(conn.last_insert_rowid())
 }
+
+pub fn tag_list_by_timeslice_id(conn: &Connection, timeslice_id: i64) -> Result<Vec<Tag>> {
+    let mut stmt = conn.prepare(
+        "SELECT tag_id, title FROM tag JOIN timeslice_tag USING(tag_id) WHERE timeslice_id = ?1",
+    )?;
+    let tags = stmt
+        .query_map(params![timeslice_id], |row| {
+            Ok(Tag {
+                id: row.get(0)?,
+                title: row.get(1)?,
+            })
+        })?
+        .collect();
+    tags
+}
+
+pub fn timeslice_tag_clear(conn: &Connection, timeslice_id: i64) -> Result<usize> {
+    conn.execute(
+        "DELETE FROM timeslice_tag WHERE timeslice_id = ?1",
+        params![timeslice_id],
+    )
+}
+
+/// Swaps the tag `from_tag_id` of a slice for `to_tag_id`, without linking the slice twice.
+pub fn timeslice_tag_replace(
+    conn: &Connection,
+    timeslice_id: i64,
+    from_tag_id: i64,
+    to_tag_id: i64,
+) -> Result<usize> {
+    conn.execute(
+        "
+        DELETE FROM timeslice_tag
+        WHERE
+            timeslice_id = ?1
+            AND tag_id = ?2
+            AND EXISTS(SELECT 1 FROM timeslice_tag WHERE timeslice_id = ?1 AND tag_id = ?3)
+        ",
+        params![timeslice_id, from_tag_id, to_tag_id],
+    )?;
+    conn.execute(
+        "UPDATE timeslice_tag SET tag_id = ?3 WHERE timeslice_id = ?1 AND tag_id = ?2",
+        params![timeslice_id, from_tag_id, to_tag_id],
+    )
+}
+
+/// Moves all slices tagged with `from_tag_id` to `to_tag_id`, without linking a slice twice.
+pub fn tag_relink_timeslices(conn: &Connection, from_tag_id: i64, to_tag_id: i64) -> Result<usize> {
+    conn.execute_named(
+        "
+        DELETE FROM timeslice_tag
+        WHERE
+            tag_id = :from_tag_id
+            AND timeslice_id IN (SELECT timeslice_id FROM timeslice_tag WHERE tag_id = :to_tag_id)
+        ",
+        named_params! {":from_tag_id": from_tag_id, ":to_tag_id": to_tag_id},
+    )?;
+    conn.execute_named(
+        "UPDATE timeslice_tag SET tag_id = :to_tag_id WHERE tag_id = :from_tag_id",
+        named_params! {":from_tag_id": from_tag_id, ":to_tag_id": to_tag_id},
+    )
+}
+
+// rate
+/////////////////////////////
+#[derive(Debug)]
+pub struct Rate {
+    pub project_title: String,
+    pub tag_title: Option<String>,
+    /// cents per hour
+    pub hourly_rate: i64,
+    pub currency: String,
+}
+
+pub fn rate_list(conn: &Connection) -> Result<Vec<Rate>> {
+    let mut stmt = conn.prepare(
+        "
+        SELECT project.title, NULL, hourly_rate, currency
+        FROM project_rate
+        JOIN project USING(project_id)
+        UNION ALL
+        SELECT project.title, tag.title, hourly_rate, currency
+        FROM tag_rate
+        JOIN project USING(project_id)
+        JOIN tag USING(tag_id)
+        ORDER BY 1, 2
+        ",
+    )?;
+    let rates = stmt
+        .query_map(NO_PARAMS, |row| {
+            Ok(Rate {
+                project_title: row.get(0)?,
+                tag_title: row.get(1)?,
+                hourly_rate: row.get(2)?,
+                currency: row.get(3)?,
+            })
+        })?
+        .collect();
+    rates
+}
+
+pub fn project_rate_set(
+    conn: &Connection,
+    project_id: i64,
+    hourly_rate: i64,
+    currency: &str,
+) -> Result<usize> {
+    conn.execute(
+        "INSERT OR REPLACE INTO project_rate (project_id, hourly_rate, currency) VALUES (?1, ?2, ?3)",
+        params![project_id, hourly_rate, currency],
+    )
+}
+
+pub fn project_rate_delete(conn: &Connection, project_id: i64) -> Result<usize> {
+    conn.execute(
+        "DELETE FROM project_rate WHERE project_id = ?1",
+        params![project_id],
+    )
+}
+
+pub fn tag_rate_set(
+    conn: &Connection,
+    project_id: i64,
+    tag_id: i64,
+    hourly_rate: i64,
+    currency: &str,
+) -> Result<usize> {
+    conn.execute(
+        "INSERT OR REPLACE INTO tag_rate (project_id, tag_id, hourly_rate, currency) VALUES (?1, ?2, ?3, ?4)",
+        params![project_id, tag_id, hourly_rate, currency],
+    )
+}
+
+pub fn tag_rate_delete(conn: &Connection, project_id: i64, tag_id: i64) -> Result<usize> {
+    conn.execute(
+        "DELETE FROM tag_rate WHERE project_id = ?1 AND tag_id = ?2",
+        params![project_id, tag_id],
+    )
+}
+
+// goal
+/////////////////////////////
+#[derive(Debug)]
+pub struct Goal {
+    /// `None` for a goal over all projects
+    pub project_title: Option<String>,
+    /// `day` or `week`
+    pub period: String,
+    pub minutes: i64,
+    pub since: NaiveDate,
+}
+
+pub fn goal_list(conn: &Connection) -> Result<Vec<Goal>> {
+    let mut stmt = conn.prepare(
+        "
+        SELECT project.title, period, minutes, since
+        FROM goal
+        LEFT JOIN project USING(project_id)
+        ORDER BY project.title IS NOT NULL, project.title, period
+        ",
+    )?;
+    let goals = stmt
+        .query_map(NO_PARAMS, |row| {
+            Ok(Goal {
+                project_title: row.get(0)?,
+                period: row.get(1)?,
+                minutes: row.get(2)?,
+                since: row.get(3)?,
+            })
+        })?
+        .collect();
+    goals
+}
+
+/// Sets the goal of a project, or the overall goal if `project_id` is `None`.
+pub fn goal_set(
+    conn: &Connection,
+    project_id: Option<i64>,
+    period: &str,
+    minutes: i64,
+    since: NaiveDate,
+) -> Result<usize> {
+    goal_delete(conn, project_id, period)?;
+    conn.execute(
+        "INSERT INTO goal (project_id, period, minutes, since) VALUES (?1, ?2, ?3, ?4)",
+        params![project_id, period, minutes, since],
+    )
+}
+
+pub fn goal_delete(conn: &Connection, project_id: Option<i64>, period: &str) -> Result<usize> {
+    conn.execute(
+        "DELETE FROM goal WHERE project_id IS ?1 AND period = ?2",
+        params![project_id, period],
+    )
+}
+
+// budget
+/////////////////////////////
+#[derive(Debug)]
+pub struct Budget {
+    pub project_title: String,
+    /// a budget in hours, `None` for a budget in money
+    pub minutes: Option<i64>,
+    /// cents, with `currency`
+    pub amount: Option<i64>,
+    pub currency: Option<String>,
+}
+
+pub fn budget_list(conn: &Connection) -> Result<Vec<Budget>> {
+    let mut stmt = conn.prepare(
+        "
+        SELECT project.title, minutes, amount, currency
+        FROM project_budget
+        JOIN project USING(project_id)
+        ORDER BY project.title
+        ",
+    )?;
+    let budgets = stmt
+        .query_map(NO_PARAMS, |row| {
+            Ok(Budget {
+                project_title: row.get(0)?,
+                minutes: row.get(1)?,
+                amount: row.get(2)?,
+                currency: row.get(3)?,
+            })
+        })?
+        .collect();
+    budgets
+}
+
+pub fn budget_set(
+    conn: &Connection,
+    project_id: i64,
+    minutes: Option<i64>,
+    amount: Option<(i64, &str)>,
+) -> Result<usize> {
+    conn.execute(
+        "INSERT OR REPLACE INTO project_budget (project_id, minutes, amount, currency) VALUES (?1, ?2, ?3, ?4)",
+        params![
+            project_id,
+            minutes,
+            amount.map(|(amount, _)| amount),
+            amount.map(|(_, currency)| currency)
+        ],
+    )
+}
+
+pub fn budget_delete(conn: &Connection, project_id: i64) -> Result<usize> {
+    conn.execute(
+        "DELETE FROM project_budget WHERE project_id = ?1",
+        params![project_id],
+    )
+}
+
+// absence
+/////////////////////////////
+#[derive(Debug)]
+pub struct Absence {
+    pub day: NaiveDate,
+    pub reason: Option<String>,
+}
+
+/// Lists the absences from `from` to `to`, both inclusive, `None` leaves a side open.
+pub fn absence_list(
+    conn: &Connection,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+) -> Result<Vec<Absence>> {
+    let mut stmt = conn.prepare(
+        "
+        SELECT day, reason
+        FROM absence
+        WHERE (?1 IS NULL OR day >= ?1) AND (?2 IS NULL OR day <= ?2)
+        ORDER BY day
+        ",
+    )?;
+    let absences = stmt
+        .query_map(params![from, to], |row| {
+            Ok(Absence {
+                day: row.get(0)?,
+                reason: row.get(1)?,
+            })
+        })?
+        .collect();
+    absences
+}
+
+pub fn absence_set(conn: &Connection, day: NaiveDate, reason: Option<&str>) -> Result<usize> {
+    conn.execute(
+        "INSERT OR REPLACE INTO absence (day, reason) VALUES (?1, ?2)",
+        params![day, reason],
+    )
+}
+
+pub fn absence_delete(conn: &Connection, day: NaiveDate) -> Result<usize> {
+    conn.execute("DELETE FROM absence WHERE day = ?1", params![day])
+}
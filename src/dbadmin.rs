@@ -0,0 +1,71 @@
+use rusqlite::Connection;
+use std::error::Error;
+use std::path::Path;
+use std::result::Result;
+
+use crate::backup;
+use crate::colors::Colors;
+use crate::config::Config;
+use crate::migration;
+use crate::schema;
+
+pub fn version_command(conn: &Connection, config: &Config) -> Result<(), Box<dyn Error>> {
+    let current = migration::current_version(conn)?;
+    let latest = migration::latest_version(&schema::migrations());
+
+    println!(
+        "schema version {} (latest supported: {})",
+        current.to_string().as_str().color_heading(&config.colors),
+        latest
+    );
+    if let (Some(version), true) = (migration::pinned_version(conn)?, current < latest) {
+        println!(
+            "pinned to schema version {}, run `punch db migrate` to upgrade",
+            version
+        );
+    } else if current < latest {
+        println!("{} pending migration(s)", latest - current);
+    } else if current > latest {
+        println!("database was created by a newer version of punch");
+    }
+    Ok(())
+}
+
+fn print_pin_warning(conn: &Connection) -> Result<(), Box<dyn Error>> {
+    if migration::pinned_version(conn)?.is_some() {
+        println!("other commands refuse this database until `punch db migrate` upgrades it");
+    }
+    Ok(())
+}
+
+pub fn migrate_command(
+    conn: &mut Connection,
+    db_filename: &Path,
+    target: Option<u64>,
+    config: &Config,
+) -> Result<(), Box<dyn Error>> {
+    let target = target.unwrap_or_else(|| migration::latest_version(&schema::migrations()));
+    if migration::current_version(conn)? > 0 {
+        let snapshot = backup::create_snapshot(conn, db_filename, config)?;
+        println!("database backed up to {}", snapshot.display());
+    }
+    migration::migrate_to(conn, schema::migrations(), target)?;
+    println!("database migrated to schema version {}", target);
+    print_pin_warning(conn)?;
+    Ok(())
+}
+
+pub fn rollback_command(
+    conn: &mut Connection,
+    db_filename: &Path,
+    config: &Config,
+) -> Result<(), Box<dyn Error>> {
+    if migration::current_version(conn)? > 0 {
+        let snapshot = backup::create_snapshot(conn, db_filename, config)?;
+        println!("database backed up to {}", snapshot.display());
+    }
+    let version = migration::rollback(conn, schema::migrations())?;
+    println!("database rolled back to schema version {}", version);
+    print_pin_warning(conn)?;
+    Ok(())
+}
@@ -1,7 +1,20 @@
 use chrono::{DateTime, Utc};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Filter {
     pub from: Option<DateTime<Utc>>,
     pub to: Option<DateTime<Utc>>,
+    /// a project path, matching the project and all its sub-projects
+    pub project: Option<String>,
+    /// without a project, also match slices of archived projects
+    pub archived: bool,
 }
+
+// matches `project.title` against the `:filter_project` parameter, NULL matches all projects but
+// the archived ones. A named project always matches, archived or not.
+pub const PROJECT_CONDITION: &str = "
+    (
+        (:filter_project IS NULL AND NOT project.archived)
+        OR project.title = :filter_project
+        OR substr(project.title, 1, length(:filter_project) + 1) = :filter_project || '/'
+    )";
@@ -0,0 +1,220 @@
+use chrono::{Datelike, Duration, NaiveDate, TimeZone, Utc};
+use rusqlite::Connection;
+use std::error::Error;
+use std::result::Result;
+
+use crate::colors::Colors;
+use crate::config::Config;
+use crate::datetime;
+use crate::datetime::DisplayTimezone;
+use crate::db;
+use crate::filter::Filter;
+use crate::projects;
+use crate::summarize;
+use crate::summarize::{GroupingMode, PeriodSummaryGrouping};
+
+/*
+# example output:
+
+acme                 week  [████████████░░░░░░░░]     20h  0m  0s / 32h  0m  0s    62%
+                                                12h  0m  0s remaining, carried forward -4h  0m  0s
+
+*/
+
+const BAR_WIDTH: usize = 20;
+
+/// Parses a number of hours such as `32` or `7.5` into whole minutes, at least one.
+pub fn parse_hours(hours: &str) -> Result<i64, Box<dyn Error>> {
+    match hours.parse::<f64>() {
+        Ok(h) if h.is_finite() && (h * 60.0).round() >= 1.0 => Ok((h * 60.0).round() as i64),
+        _ => Err(format!(
+            "invalid number of hours {}, use e.g. 32 or 7.5, at least a minute",
+            hours
+        )
+        .into()),
+    }
+}
+
+fn parse_period(period: &str) -> Result<GroupingMode, Box<dyn Error>> {
+    match period {
+        "day" => Ok(GroupingMode::Day),
+        "week" => Ok(GroupingMode::Week),
+        _ => Err(format!("unknown goal period: {}", period).into()),
+    }
+}
+
+// daily goals only apply on the working days of the schedule, time worked on other days counts
+// as overtime
+fn is_goal_day(date: NaiveDate, config: &Config) -> bool {
+    config.schedule.hours_on(date.weekday()) > 0.0
+}
+
+pub fn progress_bar(done: Duration, goal: Duration, config: &Config) -> String {
+    let filled = if goal <= Duration::zero() {
+        BAR_WIDTH
+    } else {
+        ((done.num_seconds() * BAR_WIDTH as i64) / goal.num_seconds()).clamp(0, BAR_WIDTH as i64)
+            as usize
+    };
+    format!(
+        "[{}{}]",
+        "█".repeat(filled).as_str().color_duration(&config.colors),
+        "░".repeat(BAR_WIDTH - filled)
+    )
+}
+
+pub fn set_command(
+    conn: &mut Connection,
+    project_name: Option<&str>,
+    period: &str,
+    hours: &str,
+    since: Option<NaiveDate>,
+    tz: DisplayTimezone,
+    config: &Config,
+) -> Result<(), Box<dyn Error>> {
+    let minutes = parse_hours(hours)?;
+    let grouping_mode = parse_period(period)?;
+    // the carry forward is counted in whole periods
+    let since = match grouping_mode
+        .grouping_for_date(since.unwrap_or_else(|| datetime::today(tz)), config)
+    {
+        PeriodSummaryGrouping::Day(date) | PeriodSummaryGrouping::Week(date) => date,
+        _ => unreachable!(),
+    };
+    let tx = conn.transaction()?;
+    let project_id = match project_name {
+        Some(name) => Some(projects::get_project_or_parent(&tx, name)?.id),
+        None => None,
+    };
+    db::goal_set(&tx, project_id, period, minutes, since)?;
+    tx.commit()?;
+    println!(
+        "goal of {} set to {} per {} from {} on",
+        project_name
+            .unwrap_or("all projects")
+            .color_project(&config.colors),
+        datetime::format_duration(&Duration::minutes(minutes), config)?
+            .color_duration(&config.colors),
+        period,
+        datetime::naivedate_format(since, config).color_time(&config.colors)
+    );
+    Ok(())
+}
+
+pub fn unset_command(
+    conn: &mut Connection,
+    project_name: Option<&str>,
+    period: &str,
+    config: &Config,
+) -> Result<(), Box<dyn Error>> {
+    let tx = conn.transaction()?;
+    let project_id = match project_name {
+        Some(name) => Some(projects::get_project(&tx, name)?.id),
+        None => None,
+    };
+    let deleted = db::goal_delete(&tx, project_id, period)?;
+    tx.commit()?;
+    if deleted == 0 {
+        return Err("no goal set".into());
+    }
+    println!(
+        "removed {} goal of {}",
+        period,
+        project_name
+            .unwrap_or("all projects")
+            .color_project(&config.colors)
+    );
+    Ok(())
+}
+
+pub fn goals_command(
+    conn: &Connection,
+    tz: DisplayTimezone,
+    config: &Config,
+) -> Result<(), Box<dyn Error>> {
+    let goals = db::goal_list(conn)?;
+    if goals.is_empty() {
+        println!("No goals set. Use `punch goals set <hours> --per week` to add one.");
+        return Ok(());
+    }
+    let today = datetime::today(tz);
+
+    for goal in goals {
+        let grouping_mode = parse_period(&goal.period)?;
+        let current = grouping_mode.grouping_for_date(today, config);
+        let goal_time = Duration::minutes(goal.minutes);
+
+        // a day of margin as slices are assigned to days in the display timezone
+        let filter = Filter {
+            from: Some(Utc.from_utc_date(&goal.since).and_hms(0, 0, 0) - Duration::days(1)),
+            to: None,
+            project: goal.project_title.clone(),
+            archived: false,
+        };
+        let slices = summarize::get_summary_timeslices(conn, &filter, tz)?;
+        let totals = summarize::period_totals(&slices, &grouping_mode, true, config);
+        let done_in = |grouping: &PeriodSummaryGrouping| {
+            totals.get(grouping).copied().unwrap_or_else(Duration::zero)
+        };
+
+        // over and under time of all finished periods since the goal was set
+        let mut carried = Duration::zero();
+        let mut date = goal.since;
+        while grouping_mode.grouping_for_date(date, config) < current {
+            let grouping = grouping_mode.grouping_for_date(date, config);
+            carried = carried + done_in(&grouping);
+            if !matches!(grouping_mode, GroupingMode::Day) || is_goal_day(date, config) {
+                carried = carried - goal_time;
+            }
+            date = match grouping_mode {
+                GroupingMode::Week => date + Duration::weeks(1),
+                _ => date + Duration::days(1),
+            };
+        }
+
+        let done = done_in(&current);
+        let applies_today =
+            !matches!(grouping_mode, GroupingMode::Day) || is_goal_day(today, config);
+        let percent = if applies_today {
+            format!("{:>4}%", done.num_seconds() * 100 / goal_time.num_seconds())
+        } else {
+            String::from("  day off")
+        };
+        println!(
+            "{title} {period:<5} {bar}  {done:>14} / {goal}  {percent}",
+            title = format!(
+                "{:<20}",
+                goal.project_title.as_deref().unwrap_or("all projects")
+            )
+            .as_str()
+            .color_project(&config.colors),
+            period = goal.period,
+            bar = progress_bar(
+                done,
+                if applies_today {
+                    goal_time
+                } else {
+                    Duration::zero()
+                },
+                config
+            ),
+            done = datetime::format_duration(&done, config)?.color_duration(&config.colors),
+            goal = datetime::format_duration(&goal_time, config)?,
+            percent = percent
+        );
+        let remaining = if applies_today {
+            (goal_time - done).max(Duration::zero())
+        } else {
+            Duration::zero()
+        };
+        println!(
+            "{:<48}{} remaining, carried forward {}",
+            "",
+            datetime::format_duration(&remaining, config)?
+                .trim()
+                .color_duration(&config.colors),
+            datetime::format_signed_duration(&carried, config)?.color_duration(&config.colors)
+        );
+    }
+    Ok(())
+}
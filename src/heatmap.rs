@@ -0,0 +1,134 @@
+use chrono::{Datelike, Duration, NaiveDate, TimeZone, Utc};
+use colored::Colorize;
+use rusqlite::Connection;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::result::Result;
+
+use crate::colors::Colors;
+use crate::config::Config;
+use crate::datetime;
+use crate::datetime::DisplayTimezone;
+use crate::filter::Filter;
+use crate::summarize;
+use crate::summarize::{GroupingMode, PeriodSummaryGrouping};
+
+/*
+# example output:
+
+2020
+     Jan Feb Mar  Apr ...
+Mon  ·░▒▓█·░▒▓█·░▒▓█·░▒▓█ ...
+Tue  ·░▒▓█·░▒▓█·░▒▓█·░▒▓█ ...
+...
+Sun  ·········· ...
+
+less ·░▒▓█ more    1234h  0m  0s on 187 day(s), at most 10h 15m  0s a day
+
+*/
+
+// from no time to the longest day of the year
+const SHADES: [&str; 5] = ["·", "░", "▒", "▓", "█"];
+
+fn shade(time: Duration, max: Duration, config: &Config) -> String {
+    if time <= Duration::zero() {
+        return SHADES[0].dimmed().to_string();
+    }
+    let level = ((time.num_seconds() * 4 + max.num_seconds() - 1) / max.num_seconds()).clamp(1, 4);
+    SHADES[level as usize]
+        .color_time(&config.colors)
+        .to_string()
+}
+
+/// Prints the time of each day of `year` with weeks as columns and weekdays as rows.
+pub fn heatmap_command(
+    conn: &Connection,
+    year: i32,
+    project: Option<&str>,
+    tz: DisplayTimezone,
+    config: &Config,
+) -> Result<(), Box<dyn Error>> {
+    let first = NaiveDate::from_ymd(year, 1, 1);
+    let last = NaiveDate::from_ymd(year, 12, 31);
+    // a day of margin as slices are assigned to days in the display timezone
+    let filter = Filter {
+        from: Some(Utc.from_utc_date(&first).and_hms(0, 0, 0) - Duration::days(1)),
+        to: Some(Utc.from_utc_date(&last).and_hms(0, 0, 0) + Duration::days(2)),
+        project: project.map(String::from),
+        archived: false,
+    };
+    let slices = summarize::get_summary_timeslices(conn, &filter, tz)?;
+    let days = summarize::period_totals(&slices, &GroupingMode::Day, true, config)
+        .into_iter()
+        .filter_map(|(grouping, time)| match grouping {
+            PeriodSummaryGrouping::Day(day) if day.year() == year => Some((day, time)),
+            _ => None,
+        })
+        .collect::<BTreeMap<_, _>>();
+    let max = days.values().copied().max().unwrap_or_else(Duration::zero);
+    let total = days
+        .values()
+        .fold(Duration::zero(), |sum, time| sum + *time);
+
+    let start = datetime::week_start(first, config);
+    let weeks = ((last - start).num_days() / 7 + 1) as usize;
+
+    println!("{}", year.to_string().color_heading(&config.colors));
+    // the name of a month above the week of its first day, if there is room for it
+    let mut months = String::new();
+    for week in 0..weeks {
+        let first_of_week = start + Duration::weeks(week as i64);
+        match (0..7)
+            .map(|i| first_of_week + Duration::days(i))
+            .find(|day| day.day() == 1 && day.year() == year)
+        {
+            Some(day) if months.chars().count() <= week => {
+                months.push_str(&" ".repeat(week - months.chars().count()));
+                months.push_str(&day.format("%b").to_string());
+            }
+            _ => {}
+        }
+    }
+    println!("     {}", months);
+
+    for weekday in 0..7 {
+        let row = (0..weeks)
+            .map(|week| {
+                let day = start + Duration::weeks(week as i64) + Duration::days(weekday);
+                if day.year() != year {
+                    String::from(" ")
+                } else {
+                    shade(
+                        days.get(&day).copied().unwrap_or_else(Duration::zero),
+                        max,
+                        config,
+                    )
+                }
+            })
+            .collect::<String>();
+        println!(
+            "{}  {}",
+            (start + Duration::days(weekday)).format("%a"),
+            row
+        );
+    }
+
+    println!(
+        "\nless {} more    {} on {} day(s), at most {} a day",
+        SHADES
+            .iter()
+            .enumerate()
+            .map(|(i, s)| if i == 0 {
+                s.dimmed().to_string()
+            } else {
+                s.color_time(&config.colors).to_string()
+            })
+            .collect::<String>(),
+        datetime::format_duration(&total, config)?
+            .trim()
+            .color_duration(&config.colors),
+        days.values().filter(|time| !time.is_zero()).count(),
+        datetime::format_duration(&max, config)?.trim()
+    );
+    Ok(())
+}
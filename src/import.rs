@@ -1,6 +1,5 @@
 // use serde_json::Result;
 use chrono::{DateTime, TimeZone, Utc};
-use rusqlite;
 use rusqlite::Connection;
 use std::convert::From;
 use std::error::Error;
@@ -33,18 +32,21 @@ impl From<RawFrame> for Frame {
 }
 
 fn import_watson_frame(conn: &Connection, frame: Frame) -> rusqlite::Result<()> {
-    let project_id = match db::project_get_by_name(&conn, &frame.project)? {
-        None => db::project_create(&conn, &frame.project)?,
-        Some(p) => p.id,
+    let (project_id, billable) = match db::project_get_by_name(conn, &frame.project)? {
+        None => (db::project_create(conn, &frame.project)?, true),
+        Some(p) => (p.id, p.billable),
     };
 
     let timeslice_id = db::timeslice_create(
-        &conn,
+        conn,
         db::Timeslice {
             id: None,
-            project_id: project_id,
+            project_id,
             started_on: frame.start,
             stopped_on: Some(frame.stop),
+            utc_offset: None,
+            note: None,
+            billable,
         },
     )?;
 
@@ -53,7 +55,7 @@ fn import_watson_frame(conn: &Connection, frame: Frame) -> rusqlite::Result<()>
             conn,
             db::TagCreate {
                 title: tag,
-                project_id,
+                project_id: Some(project_id),
             },
         )?;
         println!("tag id: {}", tag_id);
@@ -76,7 +78,7 @@ pub fn import_watson_frames(conn: &mut Connection, file_name: &str) -> Result<()
     frames
         .clone()
         .into_iter()
-        .map(|raw| Frame::from(raw))
+        .map(Frame::from)
         .for_each(|frame| {
             import_watson_frame(&tx, frame).unwrap();
         });
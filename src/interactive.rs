@@ -0,0 +1,780 @@
+use chrono::{DateTime, Duration, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use crossterm::cursor::Show;
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers};
+use crossterm::execute;
+use crossterm::terminal::{
+    disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
+};
+use itertools::Itertools;
+use rusqlite::Connection;
+use std::error::Error;
+use std::io;
+use std::panic;
+use std::result::Result;
+use tui::backend::{Backend, CrosstermBackend};
+use tui::layout::{Constraint, Direction, Layout};
+use tui::style::{Modifier, Style};
+use tui::text::{Span, Spans};
+use tui::widgets::{Block, Borders, List, ListItem, ListState, Paragraph};
+use tui::{Frame, Terminal};
+
+use crate::billing;
+use crate::colors;
+use crate::config::Config;
+use crate::datetime;
+use crate::datetime::DisplayTimezone;
+use crate::db;
+use crate::filter::Filter;
+use crate::log;
+use crate::log::LogTimeslice;
+use crate::startstop;
+
+/*
+# layout:
+
+┌punch───────────────────────────────────────────────────────────────┐
+│running website since 15:26, 2h 44m 10s                             │
+└────────────────────────────────────────────────────────────────────┘
+Sat 12 September 2020                          6h 29m  0s
+    08:20 — 12:05       3h 45m  0s  website (backend, admin)  #41
+  > 15:26 — running     2h 44m 10s  website (frontend)  #42
+┌────────────────────────────────────────────────────────────────────┐
+│↑/↓ move  e edit  s split  m merge with next  d delete  q quit      │
+└────────────────────────────────────────────────────────────────────┘
+
+*/
+
+const FIELDS: [&str; 4] = ["start", "stop", "project", "tags"];
+// how often the running slice is redrawn
+const TICK_MILLISECONDS: u64 = 1000;
+
+enum Mode {
+    Browse,
+    Edit {
+        fields: Vec<String>,
+        focus: usize,
+    },
+    Split(String),
+    /// confirms merging the selected slice with the next one, showing the gap booked as work
+    Merge(Duration),
+    Delete,
+}
+
+struct App {
+    filter: Filter,
+    tz: DisplayTimezone,
+    days: Vec<(NaiveDate, Vec<LogTimeslice>)>,
+    /// index of the selected slice, counted over all days
+    selected: usize,
+    mode: Mode,
+    /// the outcome of the last action, or its error
+    message: Option<(String, bool)>,
+}
+
+// the time of a slice so far, the running slice ends now
+fn slice_duration(slice: &LogTimeslice) -> Duration {
+    if slice.running {
+        Utc::now().signed_duration_since(slice.started_on)
+    } else {
+        slice.duration
+    }
+}
+
+fn format_time(dt: &DateTime<FixedOffset>) -> String {
+    dt.format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+// parses `YYYY-MM-DD HH:MM[:SS]`, or `HH:MM[:SS]` on the day of `default`, in the offset of
+// `default`
+fn parse_time(
+    input: &str,
+    default: DateTime<FixedOffset>,
+) -> Result<DateTime<Utc>, Box<dyn Error>> {
+    let input = input.trim();
+    let naive = ["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(input, format).ok())
+        .or_else(|| {
+            ["%H:%M:%S", "%H:%M"]
+                .iter()
+                .find_map(|format| NaiveTime::parse_from_str(input, format).ok())
+                .map(|time| default.naive_local().date().and_time(time))
+        })
+        .ok_or_else(|| format!("invalid time {}, use HH:MM or YYYY-MM-DD HH:MM", input))?;
+    Ok(datetime::as_utc(
+        default.offset().from_local_datetime(&naive).unwrap(),
+    ))
+}
+
+fn parse_tags(input: &str) -> Vec<&str> {
+    input
+        .split(',')
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+        .unique()
+        .collect()
+}
+
+fn assign_tags(
+    conn: &Connection,
+    timeslice_id: i64,
+    project_id: i64,
+    tags: &[&str],
+) -> Result<(), Box<dyn Error>> {
+    for tag in tags {
+        let tag_id = db::tag_get_id_or_create(
+            conn,
+            db::TagCreate {
+                project_id: Some(project_id),
+                title: tag.to_string(),
+            },
+        )?;
+        db::tag_assign_to_timeslice(
+            conn,
+            db::TimesliceTagCreate {
+                tag_id,
+                timeslice_id,
+            },
+        )?;
+    }
+    Ok(())
+}
+
+fn check_times(
+    started_on: DateTime<Utc>,
+    stopped_on: Option<DateTime<Utc>>,
+) -> Result<(), Box<dyn Error>> {
+    let now = Utc::now();
+    match stopped_on {
+        Some(stopped_on) if stopped_on <= started_on => {
+            Err("the slice must stop after it started".into())
+        }
+        Some(stopped_on) if stopped_on > now => Err("the slice cannot stop in the future".into()),
+        None if started_on > now => Err("the slice cannot start in the future".into()),
+        _ => Ok(()),
+    }
+}
+
+/// Saves the edited start, stop, project and tags of `slice`.
+fn save_slice(
+    conn: &mut Connection,
+    slice: &LogTimeslice,
+    fields: &[String],
+) -> Result<(), Box<dyn Error>> {
+    let started_on = parse_time(&fields[0], slice.started_on)?;
+    let stopped_on = match fields[1].trim() {
+        "" if slice.running => None,
+        "" => return Err("a stopped slice needs a stop time".into()),
+        stop => Some(parse_time(stop, slice.stopped_on)?),
+    };
+    check_times(started_on, stopped_on)?;
+    let project_name = fields[2].trim();
+    if project_name.is_empty() {
+        return Err("a slice needs a project".into());
+    }
+
+    let tx = conn.transaction()?;
+    let mut timeslice = db::timeslice_get(&tx, slice.id)?.ok_or("slice not found")?;
+    billing::ensure_slice_unlocked(&tx, slice.id)?;
+    timeslice.project_id = match db::project_get_by_name(&tx, project_name)? {
+        Some(project) => project.id,
+        None => db::project_create(&tx, project_name)?,
+    };
+    timeslice.started_on = started_on;
+    timeslice.stopped_on = stopped_on;
+    db::timeslice_update(&tx, &timeslice)?;
+    db::timeslice_tag_clear(&tx, slice.id)?;
+    assign_tags(&tx, slice.id, timeslice.project_id, &parse_tags(&fields[3]))?;
+    tx.commit()?;
+    Ok(())
+}
+
+/// Splits `slice` at the given time, the second part gets its project, tags and billability but
+/// no note.
+fn split_slice(
+    conn: &mut Connection,
+    slice: &LogTimeslice,
+    input: &str,
+) -> Result<(), Box<dyn Error>> {
+    let at = parse_time(input, slice.started_on)?;
+    let stopped_on = if slice.running {
+        Utc::now()
+    } else {
+        datetime::as_utc(slice.stopped_on)
+    };
+    if at <= slice.started_on || at >= stopped_on {
+        return Err("the split time must be within the slice".into());
+    }
+
+    let tx = conn.transaction()?;
+    let mut first = db::timeslice_get(&tx, slice.id)?.ok_or("slice not found")?;
+    billing::ensure_slice_unlocked(&tx, slice.id)?;
+    let second = db::Timeslice {
+        id: None,
+        project_id: first.project_id,
+        started_on: at,
+        stopped_on: first.stopped_on,
+        utc_offset: first.utc_offset,
+        note: None,
+        billable: first.billable,
+    };
+    first.stopped_on = Some(at);
+    db::timeslice_update(&tx, &first)?;
+    let second_id = db::timeslice_create(&tx, second)?;
+    for tag in db::tag_list_by_timeslice_id(&tx, slice.id)? {
+        db::tag_assign_to_timeslice(
+            &tx,
+            db::TimesliceTagCreate {
+                tag_id: tag.id,
+                timeslice_id: second_id,
+            },
+        )?;
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+/// Merges `next` into `slice`, covering both and the gap between them, with the tags of both and
+/// their notes joined.
+fn merge_slices(
+    conn: &mut Connection,
+    slice: &LogTimeslice,
+    next: &LogTimeslice,
+) -> Result<(), Box<dyn Error>> {
+    if slice.project_name != next.project_name {
+        return Err(format!(
+            "cannot merge slices of {} and {}",
+            slice.project_name, next.project_name
+        )
+        .into());
+    }
+
+    let tx = conn.transaction()?;
+    let mut merged = db::timeslice_get(&tx, slice.id)?.ok_or("slice not found")?;
+    let other = db::timeslice_get(&tx, next.id)?.ok_or("slice not found")?;
+    billing::ensure_slice_unlocked(&tx, slice.id)?;
+    billing::ensure_slice_unlocked(&tx, next.id)?;
+    merged.started_on = merged.started_on.min(other.started_on);
+    merged.stopped_on = match (merged.stopped_on, other.stopped_on) {
+        (Some(a), Some(b)) => Some(a.max(b)),
+        _ => None,
+    };
+    merged.note = match (merged.note, other.note) {
+        (Some(a), Some(b)) => Some(format!("{}\n{}", a, b)),
+        (a, b) => a.or(b),
+    };
+    let tags = db::tag_list_by_timeslice_id(&tx, slice.id)?
+        .into_iter()
+        .map(|tag| tag.id)
+        .collect::<Vec<_>>();
+    let other_tags = db::tag_list_by_timeslice_id(&tx, next.id)?;
+
+    db::timeslice_delete(&tx, next.id)?;
+    db::timeslice_update(&tx, &merged)?;
+    for tag in other_tags.into_iter().filter(|tag| !tags.contains(&tag.id)) {
+        db::tag_assign_to_timeslice(
+            &tx,
+            db::TimesliceTagCreate {
+                tag_id: tag.id,
+                timeslice_id: slice.id,
+            },
+        )?;
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+fn delete_slice(conn: &mut Connection, slice: &LogTimeslice) -> Result<(), Box<dyn Error>> {
+    let tx = conn.transaction()?;
+    db::timeslice_get(&tx, slice.id)?.ok_or("slice not found")?;
+    billing::ensure_slice_unlocked(&tx, slice.id)?;
+    db::timeslice_delete(&tx, slice.id)?;
+    tx.commit()?;
+    Ok(())
+}
+
+impl App {
+    fn slices(&self) -> impl Iterator<Item = &LogTimeslice> {
+        self.days.iter().flat_map(|(_, slices)| slices)
+    }
+
+    fn slice_count(&self) -> usize {
+        self.days.iter().map(|(_, slices)| slices.len()).sum()
+    }
+
+    fn selected_slice(&self) -> Option<&LogTimeslice> {
+        self.slices().nth(self.selected)
+    }
+
+    // the selected slice and the one following it on the same day
+    fn merge_candidates(&self) -> Result<(&LogTimeslice, &LogTimeslice), Box<dyn Error>> {
+        let slice = self.selected_slice().ok_or("no slice selected")?;
+        let next = self
+            .days
+            .iter()
+            .find(|(day, _)| *day == slice.day)
+            .and_then(|(_, slices)| slices.iter().skip_while(|s| s.id != slice.id).nth(1))
+            .ok_or("no later slice on the same day to merge with")?;
+        if slice.project_name != next.project_name {
+            return Err(format!(
+                "cannot merge slices of {} and {}",
+                slice.project_name, next.project_name
+            )
+            .into());
+        }
+        Ok((slice, next))
+    }
+
+    /// Reloads the slices, keeping the selected slice selected if it is still there.
+    fn load(&mut self, conn: &Connection) -> Result<(), Box<dyn Error>> {
+        let selected_id = self.selected_slice().map(|slice| slice.id);
+        let mut slices = log::get_filtered_log_timeslices(conn, &self.filter, self.tz)?;
+        slices.sort_by_key(|slice| (slice.day, slice.started_on));
+        self.days = slices
+            .into_iter()
+            .group_by(|slice| slice.day)
+            .into_iter()
+            .map(|(day, day_slices)| (day, day_slices.collect()))
+            .collect();
+
+        let count = self.slice_count();
+        self.selected = match selected_id.and_then(|id| self.slices().position(|s| s.id == id)) {
+            Some(index) => index,
+            None => self.selected.min(count.saturating_sub(1)),
+        };
+        Ok(())
+    }
+
+    // runs an action on the database and reloads, keeping its error for the status line
+    fn apply<F>(
+        &mut self,
+        conn: &mut Connection,
+        done: &str,
+        action: F,
+    ) -> Result<(), Box<dyn Error>>
+    where
+        F: FnOnce(&mut Connection, &App) -> Result<(), Box<dyn Error>>,
+    {
+        self.message = Some(match action(conn, self) {
+            Ok(()) => (String::from(done), false),
+            Err(e) => (e.to_string(), true),
+        });
+        self.mode = Mode::Browse;
+        self.load(conn)
+    }
+
+    /// Handles a key, returns `false` to quit.
+    fn handle_key(&mut self, conn: &mut Connection, key: KeyEvent) -> Result<bool, Box<dyn Error>> {
+        if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+            return Ok(false);
+        }
+        match &mut self.mode {
+            Mode::Browse => return self.handle_browse_key(conn, key),
+            Mode::Edit { fields, focus } => match key.code {
+                KeyCode::Esc => self.mode = Mode::Browse,
+                KeyCode::Tab | KeyCode::Down => *focus = (*focus + 1) % FIELDS.len(),
+                KeyCode::BackTab | KeyCode::Up => {
+                    *focus = (*focus + FIELDS.len() - 1) % FIELDS.len()
+                }
+                KeyCode::Backspace => {
+                    fields[*focus].pop();
+                }
+                KeyCode::Char(c) => fields[*focus].push(c),
+                KeyCode::Enter => {
+                    let fields = fields.clone();
+                    self.apply(conn, "slice saved", |conn, app| {
+                        save_slice(
+                            conn,
+                            app.selected_slice().ok_or("no slice selected")?,
+                            &fields,
+                        )
+                    })?;
+                }
+                _ => {}
+            },
+            Mode::Split(input) => match key.code {
+                KeyCode::Esc => self.mode = Mode::Browse,
+                KeyCode::Backspace => {
+                    input.pop();
+                }
+                KeyCode::Char(c) => input.push(c),
+                KeyCode::Enter => {
+                    let input = input.clone();
+                    self.apply(conn, "slice split", |conn, app| {
+                        split_slice(
+                            conn,
+                            app.selected_slice().ok_or("no slice selected")?,
+                            &input,
+                        )
+                    })?;
+                }
+                _ => {}
+            },
+            Mode::Merge(_) => match key.code {
+                KeyCode::Char('y') => self.apply(conn, "slices merged", |conn, app| {
+                    let (slice, next) = app.merge_candidates()?;
+                    merge_slices(conn, slice, next)
+                })?,
+                _ => self.mode = Mode::Browse,
+            },
+            Mode::Delete => match key.code {
+                KeyCode::Char('y') => self.apply(conn, "slice deleted", |conn, app| {
+                    delete_slice(conn, app.selected_slice().ok_or("no slice selected")?)
+                })?,
+                _ => self.mode = Mode::Browse,
+            },
+        }
+        Ok(true)
+    }
+
+    fn handle_browse_key(
+        &mut self,
+        conn: &mut Connection,
+        key: KeyEvent,
+    ) -> Result<bool, Box<dyn Error>> {
+        let count = self.slice_count();
+        self.message = None;
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(false),
+            KeyCode::Up | KeyCode::Char('k') => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => {
+                self.selected = (self.selected + 1).min(count.saturating_sub(1))
+            }
+            KeyCode::Home | KeyCode::Char('g') => self.selected = 0,
+            KeyCode::End | KeyCode::Char('G') => self.selected = count.saturating_sub(1),
+            KeyCode::Char('r') => self.load(conn)?,
+            KeyCode::Char('e') => {
+                if let Some(slice) = self.selected_slice() {
+                    self.mode = Mode::Edit {
+                        fields: vec![
+                            format_time(&slice.started_on),
+                            if slice.running {
+                                String::new()
+                            } else {
+                                format_time(&slice.stopped_on)
+                            },
+                            slice.project_name.clone(),
+                            slice.tags.join(", "),
+                        ],
+                        focus: 0,
+                    };
+                }
+            }
+            KeyCode::Char('s') => {
+                if let Some(slice) = self.selected_slice() {
+                    let middle = slice.started_on + slice_duration(slice) / 2;
+                    self.mode = Mode::Split(format_time(&middle));
+                }
+            }
+            KeyCode::Char('m') => {
+                let gap = self.merge_candidates().map(|(slice, next)| {
+                    (next.started_on - slice.stopped_on).max(Duration::zero())
+                });
+                match gap {
+                    Ok(gap) => self.mode = Mode::Merge(gap),
+                    Err(e) => self.message = Some((e.to_string(), true)),
+                }
+            }
+            KeyCode::Char('d') if self.selected_slice().is_some() => self.mode = Mode::Delete,
+            _ => {}
+        }
+        Ok(true)
+    }
+}
+
+fn running_header(conn: &Connection, config: &Config) -> Result<Spans<'static>, Box<dyn Error>> {
+    let theme = &config.colors;
+    Ok(match startstop::get_running_slice(conn)? {
+        Some(slice) => Spans::from(vec![
+            Span::raw("running "),
+            Span::styled(slice.project_name, colors::theme_style(&theme.project)),
+            Span::raw(format!(
+                " since {}, ",
+                datetime::datetime_as_time_string(&slice.started_on, config)
+            )),
+            Span::styled(
+                datetime::format_duration(
+                    &(Utc::now() - datetime::as_utc(slice.started_on)),
+                    config,
+                )?
+                .trim()
+                .to_string(),
+                colors::theme_style(&theme.duration),
+            ),
+        ]),
+        None => Spans::from("no running slice"),
+    })
+}
+
+// the list of days and slices, and the row of the selected slice
+fn list_items(
+    app: &App,
+    config: &Config,
+) -> Result<(Vec<ListItem<'static>>, Option<usize>), Box<dyn Error>> {
+    let theme = &config.colors;
+    let mut items = vec![];
+    let mut selected_row = None;
+    let mut index = 0;
+    for (day, slices) in &app.days {
+        let total = slices
+            .iter()
+            .fold(Duration::zero(), |sum, slice| sum + slice_duration(slice));
+        items.push(ListItem::new(Spans::from(vec![
+            Span::styled(
+                format!("{:<40}", datetime::naivedate_format(*day, config)),
+                colors::theme_style(&theme.heading),
+            ),
+            Span::styled(
+                datetime::format_duration(&total, config)?,
+                colors::theme_style(&theme.duration),
+            ),
+        ])));
+        for slice in slices {
+            if index == app.selected {
+                selected_row = Some(items.len());
+            }
+            index += 1;
+            let mut spans = vec![
+                Span::styled(
+                    datetime::datetime_as_time_string(&slice.started_on, config),
+                    colors::theme_style(&theme.time),
+                ),
+                Span::raw(" — "),
+                if slice.running {
+                    Span::styled(
+                        format!("{:<8}", "running"),
+                        colors::theme_style(&theme.warning),
+                    )
+                } else {
+                    Span::styled(
+                        datetime::datetime_as_time_string(&slice.stopped_on, config),
+                        colors::theme_style(&theme.time),
+                    )
+                },
+                Span::raw("  "),
+                Span::styled(
+                    datetime::format_duration(&slice_duration(slice), config)?,
+                    colors::theme_style(&theme.duration),
+                ),
+                Span::raw("  "),
+                Span::styled(
+                    slice.project_name.clone(),
+                    colors::theme_style(&theme.project),
+                ),
+            ];
+            if !slice.tags.is_empty() {
+                spans.push(Span::raw(" ("));
+                spans.push(Span::styled(
+                    slice.tags.join(", "),
+                    colors::theme_style(&theme.tag),
+                ));
+                spans.push(Span::raw(")"));
+            }
+            spans.push(Span::raw("  "));
+            spans.push(Span::styled(
+                format!("#{}", slice.id),
+                colors::theme_style(&theme.id),
+            ));
+            if let Some(note) = &slice.note {
+                spans.push(Span::raw("  "));
+                spans.push(Span::styled(
+                    note.replace('\n', " / "),
+                    colors::theme_style(&theme.note),
+                ));
+            }
+            items.push(ListItem::new(Spans::from(spans)));
+        }
+    }
+    Ok((items, selected_row))
+}
+
+fn draw<B: Backend>(
+    f: &mut Frame<B>,
+    conn: &Connection,
+    app: &App,
+    config: &Config,
+) -> Result<(), Box<dyn Error>> {
+    let footer_height = match app.mode {
+        Mode::Edit { .. } => FIELDS.len() as u16 + 2,
+        _ => 3,
+    };
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(3),
+            Constraint::Min(1),
+            Constraint::Length(footer_height),
+        ])
+        .split(f.size());
+
+    f.render_widget(
+        Paragraph::new(running_header(conn, config)?)
+            .block(Block::default().borders(Borders::ALL).title("punch")),
+        chunks[0],
+    );
+
+    let (items, selected_row) = list_items(app, config)?;
+    let mut state = ListState::default();
+    state.select(selected_row);
+    f.render_stateful_widget(
+        List::new(items)
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+            .highlight_symbol("> "),
+        chunks[1],
+        &mut state,
+    );
+
+    let footer = Block::default().borders(Borders::ALL);
+    let area = chunks[2];
+    match &app.mode {
+        Mode::Browse => {
+            let text = match &app.message {
+                Some((message, true)) => Spans::from(Span::styled(
+                    message.clone(),
+                    colors::theme_style(&config.colors.warning),
+                )),
+                Some((message, false)) => Spans::from(message.clone()),
+                None => Spans::from(
+                    "↑/↓ move  e edit  s split  m merge with next  d delete  r reload  q quit",
+                ),
+            };
+            f.render_widget(Paragraph::new(text).block(footer), area);
+        }
+        Mode::Edit { fields, focus } => {
+            let lines = FIELDS
+                .iter()
+                .zip(fields)
+                .enumerate()
+                .map(|(i, (name, value))| {
+                    let style = if i == *focus {
+                        Style::default().add_modifier(Modifier::BOLD)
+                    } else {
+                        Style::default()
+                    };
+                    Spans::from(vec![
+                        Span::styled(format!("{:<9}", name), style),
+                        Span::raw(value.clone()),
+                    ])
+                })
+                .collect::<Vec<_>>();
+            f.render_widget(
+                Paragraph::new(lines).block(footer.title(
+                    "edit: tab next field, enter save, esc cancel, empty stop keeps it running",
+                )),
+                area,
+            );
+            f.set_cursor(
+                area.x + 10 + fields[*focus].chars().count() as u16,
+                area.y + 1 + *focus as u16,
+            );
+        }
+        Mode::Split(input) => {
+            let prompt = "split at ";
+            f.render_widget(
+                Paragraph::new(Spans::from(vec![
+                    Span::raw(prompt),
+                    Span::raw(input.clone()),
+                ]))
+                .block(footer.title("split: enter split, esc cancel")),
+                area,
+            );
+            f.set_cursor(
+                area.x + 1 + (prompt.len() + input.chars().count()) as u16,
+                area.y + 1,
+            );
+        }
+        Mode::Merge(gap) => {
+            let id = app.selected_slice().map(|slice| slice.id).unwrap_or(0);
+            f.render_widget(
+                Paragraph::new(format!(
+                    "merge slice #{} with the next one, booking the gap of {} between them as work? (y/n)",
+                    id,
+                    datetime::format_duration(gap, config)?.trim()
+                ))
+                .block(footer),
+                area,
+            );
+        }
+        Mode::Delete => {
+            let id = app.selected_slice().map(|slice| slice.id).unwrap_or(0);
+            f.render_widget(
+                Paragraph::new(format!("delete slice #{}? (y/n)", id)).block(footer),
+                area,
+            );
+        }
+    }
+    Ok(())
+}
+
+fn restore_terminal() {
+    // nothing left to do if this fails, the terminal is as good as it gets
+    let _ = disable_raw_mode();
+    let _ = execute!(io::stdout(), LeaveAlternateScreen, Show);
+}
+
+/// Switches the terminal to raw mode and the alternate screen, and back when dropped. A panic
+/// restores the terminal before its message is printed, so it stays readable.
+struct TerminalGuard;
+
+impl TerminalGuard {
+    fn new() -> Result<TerminalGuard, Box<dyn Error>> {
+        let default_hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            restore_terminal();
+            default_hook(info);
+        }));
+        enable_raw_mode()?;
+        let guard = TerminalGuard;
+        execute!(io::stdout(), EnterAlternateScreen)?;
+        Ok(guard)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        restore_terminal();
+    }
+}
+
+fn run<B: Backend>(
+    terminal: &mut Terminal<B>,
+    conn: &mut Connection,
+    app: &mut App,
+    config: &Config,
+) -> Result<(), Box<dyn Error>> {
+    loop {
+        let mut result = Ok(());
+        terminal.draw(|f| result = draw(f, conn, app, config))?;
+        result?;
+        if event::poll(std::time::Duration::from_millis(TICK_MILLISECONDS))? {
+            if let Event::Key(key) = event::read()? {
+                if !app.handle_key(conn, key)? {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+/// Shows the slices matching `filter` full-screen to browse, edit, split, merge and delete them.
+pub fn interactive_command(
+    conn: &mut Connection,
+    filter: &Filter,
+    tz: DisplayTimezone,
+    config: &Config,
+) -> Result<(), Box<dyn Error>> {
+    let mut app = App {
+        filter: filter.clone(),
+        tz,
+        days: vec![],
+        selected: 0,
+        mode: Mode::Browse,
+        message: None,
+    };
+    app.load(conn)?;
+    app.selected = app.slice_count().saturating_sub(1);
+
+    let _guard = TerminalGuard::new()?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
+    run(&mut terminal, conn, &mut app, config)
+}
@@ -0,0 +1,217 @@
+use chrono::{Duration, NaiveDate, TimeZone, Utc};
+use rusqlite::Connection;
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::result::Result;
+
+use crate::colors::Colors;
+use crate::config::Config;
+use crate::datetime;
+use crate::datetime::{DisplayTimezone, RoundedSum, Rounding};
+use crate::db;
+use crate::filter::Filter;
+use crate::projects;
+use crate::rates;
+use crate::summarize;
+use crate::summarize::{GroupingMode, PeriodSummaryGrouping, SummaryTimeslice};
+
+/*
+# example output:
+
+acme, September 2020
+
+    acme/website                  12.50 h  x   95.00 EUR     1187.50 EUR
+    acme/website (backend)         3.25 h  x  110.00 EUR      357.50 EUR
+    acme/support                   1.00 h    no rate
+
+    Total                         16.75 h                    1545.00 EUR
+    Not billable                   2.00 h
+
+*/
+
+// (rate in cents per hour, currency)
+pub type HourlyRate = (i64, String);
+
+/// Rates keyed by project and tag, `None` for the rate of the project itself.
+pub type RateMap = HashMap<(String, Option<String>), HourlyRate>;
+
+pub fn rate_map(conn: &Connection) -> Result<RateMap, Box<dyn Error>> {
+    Ok(db::rate_list(conn)?
+        .into_iter()
+        .map(|rate| {
+            (
+                (rate.project_title, rate.tag_title),
+                (rate.hourly_rate, rate.currency),
+            )
+        })
+        .collect())
+}
+
+/// The most specific rate for a slice: starting at its own project and moving up to the parents,
+/// a rate for one of its tags wins over the rate of the project itself.
+pub fn line_item_for<'a>(
+    slice: &'a SummaryTimeslice,
+    rates: &RateMap,
+) -> (Option<&'a str>, Option<HourlyRate>) {
+    let mut tags = slice
+        .tags
+        .iter()
+        .map(|t| t.title.as_str())
+        .collect::<Vec<_>>();
+    tags.sort_unstable();
+    for project in projects::project_ancestors(&slice.project_title)
+        .iter()
+        .rev()
+    {
+        for tag in &tags {
+            if let Some(rate) = rates.get(&(project.to_string(), Some(tag.to_string()))) {
+                return (Some(tag), Some(rate.clone()));
+            }
+        }
+        if let Some(rate) = rates.get(&(project.to_string(), None)) {
+            return (None, Some(rate.clone()));
+        }
+    }
+    (None, None)
+}
+
+// durations are billed in hundredths of an hour
+fn rounded_hours(duration: Duration) -> i64 {
+    (duration.num_seconds() + 18) / 36
+}
+
+fn format_hours(hundredths: i64) -> String {
+    format!("{}.{:02} h", hundredths / 100, hundredths % 100)
+}
+
+pub fn invoice_command(
+    conn: &Connection,
+    project_name: &str,
+    month: NaiveDate,
+    rounding: Option<Rounding>,
+    tz: DisplayTimezone,
+    config: &Config,
+) -> Result<(), Box<dyn Error>> {
+    let rounding = rounding.as_ref();
+    let rates = rate_map(conn)?;
+
+    // a day of margin as slices are assigned to days in the display timezone
+    let filter = Filter {
+        from: Some(Utc.from_utc_date(&month).and_hms(0, 0, 0) - Duration::days(1)),
+        to: Some(
+            Utc.from_utc_date(&datetime::next_month(month))
+                .and_hms(0, 0, 0)
+                + Duration::days(1),
+        ),
+        project: Some(project_name.to_string()),
+        archived: false,
+    };
+
+    let mut line_items: BTreeMap<(String, Option<String>), (RoundedSum, Option<HourlyRate>)> =
+        BTreeMap::new();
+    let mut non_billable_time = RoundedSum::new();
+    // the running slice is only invoiced once it is stopped
+    for slice in summarize::get_summary_timeslices(conn, &filter, tz)?
+        .into_iter()
+        .filter(|slice| !slice.running)
+    {
+        let pieces = slice
+            .period_pieces(&GroupingMode::Month, true, config)
+            .into_iter()
+            .filter(|(grouping, _, _)| *grouping == PeriodSummaryGrouping::Month(month))
+            .collect::<Vec<_>>();
+        if pieces.is_empty() {
+            continue;
+        }
+        let time = if slice.billable {
+            let (tag, rate) = line_item_for(&slice, &rates);
+            &mut line_items
+                .entry((slice.project_title.clone(), tag.map(String::from)))
+                .or_insert((RoundedSum::new(), rate))
+                .0
+        } else {
+            &mut non_billable_time
+        };
+        for (_, day, duration) in pieces {
+            time.add(day, duration, rounding);
+        }
+    }
+
+    println!(
+        "{}\n",
+        format!("{}, {}", project_name, month.format("%B %Y")).color_heading(&config.colors)
+    );
+    if line_items.is_empty() && non_billable_time.raw.is_zero() {
+        println!("    No slices recorded.");
+        return Ok(());
+    }
+
+    let mut total_hours = 0;
+    let mut totals: BTreeMap<String, i64> = BTreeMap::new();
+    let mut unrated = false;
+    for ((project, tag), (time, rate)) in &line_items {
+        let hours = rounded_hours(time.rounded(rounding));
+        total_hours += hours;
+        let title = match tag {
+            Some(tag) => format!("{} ({})", project, tag),
+            None => project.to_string(),
+        };
+        let price = match rate {
+            Some((hourly_rate, currency)) => {
+                let amount = (hours * hourly_rate + 50) / 100;
+                *totals.entry(currency.to_string()).or_insert(0) += amount;
+                format!(
+                    "x {:>14} {:>15}",
+                    rates::format_amount(*hourly_rate, currency),
+                    rates::format_amount(amount, currency).color_duration(&config.colors)
+                )
+            }
+            None => {
+                unrated = true;
+                String::from("  no rate")
+            }
+        };
+        println!(
+            "    {title} {hours:>10}  {price}",
+            title = format!("{:<28}", title)
+                .as_str()
+                .color_project(&config.colors),
+            hours = format_hours(hours).color_duration(&config.colors),
+            price = price
+        );
+    }
+
+    // amounts in different currencies are totalled separately
+    for (i, (currency, total)) in totals.iter().enumerate() {
+        println!(
+            "{}    {title} {hours:>10}  {amount:>32}",
+            if i == 0 { "\n" } else { "" },
+            title =
+                format!("{:<28}", if i == 0 { "Total" } else { "" }).color_heading(&config.colors),
+            hours = if i == 0 {
+                format_hours(total_hours)
+            } else {
+                String::new()
+            },
+            amount = rates::format_amount(*total, currency).color_heading(&config.colors)
+        );
+    }
+    if totals.is_empty() {
+        println!(
+            "\n    {title} {hours:>10}",
+            title = format!("{:<28}", "Total").color_heading(&config.colors),
+            hours = format_hours(total_hours)
+        );
+    }
+    if !non_billable_time.raw.is_zero() {
+        println!(
+            "    {title} {hours:>10}",
+            title = format!("{:<28}", "Not billable").color_heading(&config.colors),
+            hours = format_hours(rounded_hours(non_billable_time.rounded(rounding)))
+        );
+    }
+    if unrated {
+        println!("\nSome time has no rate, use `punch rates set` to bill it.");
+    }
+    Ok(())
+}
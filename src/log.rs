@@ -1,139 +1,459 @@
-use chrono::{DateTime, Duration, Local, NaiveDate};
+use chrono::{DateTime, Duration, FixedOffset, NaiveDate, Utc};
 use itertools::Itertools;
-use rusqlite::{named_params, Connection};
+use rusqlite::{named_params, Connection, ToSql};
+use std::collections::HashMap;
 use std::error::Error;
 use std::result::Result;
 
 use crate::colors::Colors;
+use crate::config::BreakRule;
+use crate::config::Config;
 use crate::datetime;
+use crate::datetime::DisplayTimezone;
+use crate::filter;
 use crate::filter::Filter;
+use crate::summarize;
+use crate::summarize::{GroupingMode, PeriodSummaryGrouping, ProjectTotal};
 
 /*
 # output format:
 
 <date>:
-    <from> — <to>    <duration>  <project>  (tag_1, ..., tag_k)
+    <from> — <to>    <duration>  <project>  (tag_1, ..., tag_k)  #<id>
+        <note>
 
 # example output:
 
 2020-09-12
-    08:20 — 12:05       3h 45m  website (backend, admin)
-    15:26 — 18:10       2h 44m  website (frontend)
+    08:20 — 12:05       3h 45m  website (backend, admin)  #41
+        fix login bug
+    15:26 — 18:10       2h 44m  website (frontend)  #42
+    18:30 — running     0h 20m  website (frontend)  #43
+
+    Total               6h 49m
+      website           6h 49m
+
+Total                   6h 49m
+
+# example output with breaks:
+
+2020-09-12
+    08:20 — 12:05       3h 45m  website (backend, admin)  #41
+        fix login bug
+    12:05 — 15:26       3h 21m  break
+    15:26 — 18:10       2h 44m  website (frontend)  #42
+
+    08:20 — 18:10       6h 29m  worked, 3h 21m break
 
 */
 
 #[derive(Debug)]
-struct LogTimeslice {
-    id: i64,
-    day: NaiveDate,
-    started_on: DateTime<Local>,
-    stopped_on: DateTime<Local>,
-    duration: Duration,
-    project_name: String,
-    tags: Vec<String>,
+pub struct LogTimeslice {
+    pub id: i64,
+    pub day: NaiveDate,
+    pub started_on: DateTime<FixedOffset>,
+    pub stopped_on: DateTime<FixedOffset>,
+    pub duration: Duration,
+    pub project_name: String,
+    pub tags: Vec<String>,
+    pub note: Option<String>,
+    /// the running slice, which ends now for the time being
+    pub running: bool,
 }
 
 impl LogTimeslice {
+    #[allow(clippy::too_many_arguments)]
     fn new(
         id: i64,
-        day: &str,
         started_on: &str,
-        stopped_on: &str,
+        stopped_on: Option<&str>,
+        utc_offset: Option<i32>,
         project_name: &str,
         tags: &str,
+        note: Option<String>,
+        tz: DisplayTimezone,
     ) -> LogTimeslice {
-        let started_on = datetime::as_local(datetime::from_rfc3339_string(started_on));
-        let stopped_on = datetime::as_local(datetime::from_rfc3339_string(stopped_on));
+        let started_on = datetime::in_display_timezone(
+            datetime::as_utc(datetime::from_rfc3339_string(started_on)),
+            utc_offset,
+            tz,
+        );
+        let running = stopped_on.is_none();
+        let stopped_on = datetime::in_display_timezone(
+            match stopped_on {
+                Some(stopped_on) => datetime::as_utc(datetime::from_rfc3339_string(stopped_on)),
+                None => Utc::now(),
+            },
+            utc_offset,
+            tz,
+        );
 
         LogTimeslice {
             id,
-            day: datetime::naivedate_from_string(day),
+            day: stopped_on.naive_local().date(),
             started_on,
             stopped_on,
             duration: stopped_on - started_on,
             project_name: String::from(project_name),
-            tags: if tags.len() > 0 {
+            tags: if !tags.is_empty() {
                 tags.split(",").map(|tag| tag.to_string()).collect()
             } else {
                 vec![]
             },
+            note,
+            running,
         }
     }
 }
 
-fn group_slices_by_day(slices: Vec<LogTimeslice>) -> Vec<(NaiveDate, Vec<LogTimeslice>)> {
+impl LogTimeslice {
+    pub fn split_at_midnight(self) -> Vec<LogTimeslice> {
+        datetime::split_at_midnight(self.started_on, self.stopped_on)
+            .into_iter()
+            .map(|(started_on, stopped_on)| LogTimeslice {
+                id: self.id,
+                day: started_on.naive_local().date(),
+                started_on,
+                stopped_on,
+                duration: stopped_on - started_on,
+                project_name: self.project_name.clone(),
+                tags: self.tags.clone(),
+                note: self.note.clone(),
+                running: self.running,
+            })
+            .collect()
+    }
+}
+
+// sorted by day and start, with slices crossing midnight split first if `split` is set
+fn group_slices_by_day(
+    mut slices: Vec<LogTimeslice>,
+    split: bool,
+) -> Vec<(NaiveDate, Vec<LogTimeslice>)> {
+    if split {
+        slices = slices
+            .into_iter()
+            .flat_map(LogTimeslice::split_at_midnight)
+            .collect();
+    }
+    // with differing offsets the local days are not necessarily in utc order
+    slices.sort_by_key(|slice| (slice.day, slice.started_on));
     slices
         .into_iter()
-        .group_by(|r| r.day.clone())
+        .group_by(|r| r.day)
         .into_iter()
         .map(|(day, day_slices)| (day, day_slices.collect()))
         .collect()
 }
 
-pub fn log_command(conn: &mut Connection, filter: &Filter) -> Result<(), Box<dyn Error>> {
-    let mut stmt = conn.prepare(
+/// Loads the slices matching `condition`, an SQL expression over the `timeslice` and `project`
+/// tables which may use the given named parameters. The running slice ends now for the time being.
+pub fn get_log_timeslices(
+    conn: &Connection,
+    condition: &str,
+    params: &[(&str, &dyn ToSql)],
+    tz: DisplayTimezone,
+) -> Result<Vec<LogTimeslice>, Box<dyn Error>> {
+    let mut stmt = conn.prepare(&format!(
         "
         SELECT
             timeslice_id,
-            date(stopped_on) day,
             started_on,
             stopped_on,
+            utc_offset,
             project.title,
-            COALESCE(GROUP_CONCAT(tag.title), '')
+            COALESCE(GROUP_CONCAT(tag.title), ''),
+            note
         FROM timeslice
         JOIN project USING(project_id)
         LEFT JOIN timeslice_tag USING(timeslice_id)
-		LEFT JOIN tag USING(tag_id)
+        LEFT JOIN tag USING(tag_id)
         WHERE
-            stopped_on IS NOT NULL
-            AND stopped_on >= :filter_from_date
+            {condition}
         GROUP BY timeslice_id
-        ORDER BY day ASC
+        ORDER BY stopped_on ASC
     ",
-    )?;
-
-    let from_date = filter
-        .from
-        .unwrap_or(datetime::timestamp_1970())
-        .format(datetime::DATE_FORMAT_YMD)
-        .to_string();
+        condition = condition
+    ))?;
 
     let slices = stmt
-        .query_map_named(named_params! {":filter_from_date": from_date}, |row| {
+        .query_map_named(params, |row| {
             Ok(LogTimeslice::new(
                 row.get(0)?,
-                &*row.get::<_, String>(1)?,
-                &*row.get::<_, String>(2)?,
-                &*row.get::<_, String>(3)?,
-                &*row.get::<_, String>(4)?,
-                &*row.get::<_, String>(5)?,
+                &row.get::<_, String>(1)?,
+                row.get::<_, Option<String>>(2)?.as_deref(),
+                row.get(3)?,
+                &row.get::<_, String>(4)?,
+                &row.get::<_, String>(5)?,
+                row.get(6)?,
+                tz,
             ))
         })?
-        .map(|r| r.unwrap())
-        .collect::<Vec<LogTimeslice>>();
+        .collect::<rusqlite::Result<Vec<LogTimeslice>>>()?;
+    Ok(slices)
+}
+
+/// Loads the slices matching the filter, as listed by `log` and the tui. The `to` of the filter is
+/// not applied.
+pub fn get_filtered_log_timeslices(
+    conn: &Connection,
+    filter: &Filter,
+    tz: DisplayTimezone,
+) -> Result<Vec<LogTimeslice>, Box<dyn Error>> {
+    let from_date = filter
+        .from
+        .unwrap_or_else(datetime::timestamp_1970)
+        .format(datetime::DATE_FORMAT_YMD)
+        .to_string();
+    get_log_timeslices(
+        conn,
+        &format!(
+            "(stopped_on IS NULL OR stopped_on >= :filter_from_date) AND {}",
+            filter::PROJECT_CONDITION
+        ),
+        named_params! {":filter_from_date": from_date, ":filter_project": filter.project},
+        tz,
+    )
+}
+
+/// Options of `print_log`.
+pub struct LogOptions {
+    /// show slices crossing midnight on each day they touch
+    pub split: bool,
+    /// show the breaks between slices and the working hours of each day
+    pub breaks: bool,
+    /// warn about days without the breaks required by the configured rules
+    pub check_breaks: bool,
+    /// show the time of each project below the total of a day
+    pub by_project: bool,
+}
+
+// the gaps between the slices of a day, which must be sorted by their start
+fn breaks_of(slices: &[LogTimeslice]) -> Vec<(DateTime<FixedOffset>, DateTime<FixedOffset>)> {
+    let mut breaks = vec![];
+    let mut last_stop: Option<DateTime<FixedOffset>> = None;
+    for slice in slices {
+        match last_stop {
+            Some(stop) if slice.started_on > stop => breaks.push((stop, slice.started_on)),
+            _ => {}
+        }
+        last_stop = Some(last_stop.map_or(slice.stopped_on, |stop| stop.max(slice.stopped_on)));
+    }
+    breaks
+}
 
-    for (day, slices) in group_slices_by_day(slices) {
-        println!("{}\n", datetime::naivedate_format(day).color_heading());
+// the rule for the most working time which applies to a day with `worked` time
+fn required_break(worked: Duration, config: &Config) -> Option<&BreakRule> {
+    config
+        .breaks
+        .rules
+        .iter()
+        .filter(|rule| worked > Duration::seconds((rule.after_hours * 3600.0).round() as i64))
+        .max_by(|a, b| a.after_hours.total_cmp(&b.after_hours))
+}
+
+// `slices` are all slices of the day, also those hidden by a project filter
+fn print_day_breaks(
+    slices: &[LogTimeslice],
+    options: &LogOptions,
+    config: &Config,
+) -> Result<(), Box<dyn Error>> {
+    let worked = slices
+        .iter()
+        .fold(Duration::zero(), |sum, slice| sum + slice.duration);
+    let breaks = breaks_of(slices);
+    let break_time = breaks
+        .iter()
+        .fold(Duration::zero(), |sum, (from, to)| sum + (*to - *from));
+
+    if options.breaks {
+        let first_start = slices.iter().map(|slice| slice.started_on).min();
+        let last_stop = slices.iter().map(|slice| slice.stopped_on).max();
+        if let (Some(first_start), Some(last_stop)) = (first_start, last_stop) {
+            println!(
+                "\n    {first_start} — {last_stop} {worked:>14} worked, {break_time} break",
+                first_start = datetime::datetime_as_time_string(&first_start, config)
+                    .color_time(&config.colors),
+                last_stop = datetime::datetime_as_time_string(&last_stop, config)
+                    .color_time(&config.colors),
+                worked = datetime::format_duration(&worked, config)?.color_duration(&config.colors),
+                break_time = datetime::format_duration(&break_time, config)?.trim()
+            );
+        }
+    }
+
+    if options.check_breaks {
+        if let Some(rule) = required_break(worked, config) {
+            // short interruptions do not count as a break
+            let min_break = Duration::minutes(config.breaks.min_minutes);
+            let taken = breaks
+                .iter()
+                .map(|(from, to)| *to - *from)
+                .filter(|duration| *duration >= min_break)
+                .fold(Duration::zero(), |sum, duration| sum + duration);
+            if taken < Duration::minutes(rule.minutes) {
+                println!(
+                    "    {}",
+                    format!(
+                        "! a break of {} is required after {}h of work, {} taken",
+                        datetime::format_duration(&Duration::minutes(rule.minutes), config)?.trim(),
+                        rule.after_hours,
+                        datetime::format_duration(&taken, config)?.trim()
+                    )
+                    .color_warning(&config.colors)
+                );
+            }
+        }
+    }
+    Ok(())
+}
 
-        for slice in slices {
-            let tags = match slice.tags.len() > 0 {
-                true => format!("({})", slice.tags.join(", ").color_tag()),
+/// Prints slices grouped by day in the layout shown above. `project_totals` are the times per
+/// project and day shown with `by_project`. `all_slices` are the slices of all projects, which
+/// the breaks are taken from, as the time worked on other projects is no break.
+pub fn print_log(
+    slices: Vec<LogTimeslice>,
+    options: &LogOptions,
+    project_totals: &HashMap<NaiveDate, Vec<ProjectTotal>>,
+    all_slices: Vec<LogTimeslice>,
+    config: &Config,
+) -> Result<(), Box<dyn Error>> {
+    let mut working_days = group_slices_by_day(all_slices, options.split)
+        .into_iter()
+        .collect::<HashMap<_, _>>();
+
+    let mut total = Duration::zero();
+    for (day, slices) in group_slices_by_day(slices, options.split) {
+        println!(
+            "{}\n",
+            datetime::naivedate_format(day, config).color_heading(&config.colors)
+        );
+
+        let working_day = working_days.remove(&day).unwrap_or_default();
+        let breaks = breaks_of(&working_day);
+        for slice in &slices {
+            if options.breaks {
+                for (from, to) in breaks.iter().filter(|(_, to)| *to == slice.started_on) {
+                    println!(
+                        "    {from} — {to} {duration:>14} break",
+                        from = datetime::datetime_as_time_string(from, config)
+                            .color_time(&config.colors),
+                        to = datetime::datetime_as_time_string(to, config)
+                            .color_time(&config.colors),
+                        duration = datetime::format_duration(&(*to - *from), config)?
+                    );
+                }
+            }
+            let tags = match !slice.tags.is_empty() {
+                true => format!("({})", slice.tags.join(", ").color_tag(&config.colors)),
                 false => String::from(""),
             };
             println!(
-                "    {started_on} — {stopped_on} {duration:>14} {project_name} {tags}",
-                started_on = slice.started_on.format("%H:%M:%S").to_string().color_time(),
-                stopped_on = slice.stopped_on.format("%H:%M:%S").to_string().color_time(),
-                duration = datetime::duration_as_hms_string(&slice.duration)?
+                "    {started_on} — {stopped_on} {duration:>14} {project_name} {tags}  {id}",
+                started_on = datetime::datetime_as_time_string(&slice.started_on, config)
+                    .color_time(&config.colors),
+                stopped_on = if slice.running {
+                    // as wide as a time to keep the columns aligned
+                    format!(
+                        "{:<width$}",
+                        "running",
+                        width = datetime::datetime_as_time_string(&slice.stopped_on, config).len()
+                    )
+                    .color_warning(&config.colors)
+                } else {
+                    datetime::datetime_as_time_string(&slice.stopped_on, config)
+                        .color_time(&config.colors)
+                },
+                duration = datetime::format_duration(&slice.duration, config)?
                     .to_string()
-                    .color_duration(),
-                project_name = slice.project_name.to_string().color_project(),
-                tags = tags
+                    .color_duration(&config.colors),
+                project_name = slice.project_name.to_string().color_project(&config.colors),
+                tags = tags,
+                id = format!("#{}", slice.id).color_id(&config.colors)
             );
+            if let Some(note) = &slice.note {
+                for line in note.lines() {
+                    println!("        {}", line.color_note(&config.colors));
+                }
+            }
+        }
+        print_day_breaks(&working_day, options, config)?;
+
+        let day_total = slices
+            .iter()
+            .fold(Duration::zero(), |sum, slice| sum + slice.duration);
+        total = total + day_total;
+        // with breaks the working hours of the day are already shown, unless some are filtered out
+        if !options.breaks || working_day.len() != slices.len() {
+            println!(
+                "\n    {} {:>14}",
+                format!("{:<19}", "Total").color_heading(&config.colors),
+                datetime::format_duration(&day_total, config)?.color_duration(&config.colors)
+            );
+        }
+        if options.by_project {
+            for project in project_totals.get(&day).into_iter().flatten() {
+                println!(
+                    "      {indent}{title} {duration:>14}",
+                    indent = "  ".repeat(project.depth),
+                    title = format!(
+                        "{:<width$}",
+                        project.title,
+                        width = 17usize.saturating_sub(2 * project.depth)
+                    )
+                    .color_project(&config.colors),
+                    duration = datetime::format_duration(&project.time, config)?
+                );
+            }
         }
 
         println!("\n")
     }
+    println!(
+        "{} {:>14}",
+        format!("{:<23}", "Total").color_heading(&config.colors),
+        datetime::format_duration(&total, config)?.color_heading(&config.colors)
+    );
 
     Ok(())
 }
+
+pub fn log_command(
+    conn: &mut Connection,
+    filter: &Filter,
+    options: &LogOptions,
+    tz: DisplayTimezone,
+    config: &Config,
+) -> Result<(), Box<dyn Error>> {
+    let from_date = filter
+        .from
+        .unwrap_or(datetime::timestamp_1970())
+        .format(datetime::DATE_FORMAT_YMD)
+        .to_string();
+
+    let slices = get_filtered_log_timeslices(conn, filter, tz)?;
+    // breaks are gaps in the work on all projects, archived ones included
+    let all_slices = if options.breaks || options.check_breaks {
+        get_log_timeslices(
+            conn,
+            "stopped_on IS NULL OR stopped_on >= :filter_from_date",
+            named_params! {":filter_from_date": from_date},
+            tz,
+        )?
+    } else {
+        vec![]
+    };
+
+    let mut project_totals = HashMap::new();
+    if options.by_project {
+        let summary_slices = summarize::get_summary_timeslices(conn, filter, tz)?;
+        for (grouping, totals) in
+            summarize::project_totals(summary_slices, &GroupingMode::Day, options.split, config)
+        {
+            if let PeriodSummaryGrouping::Day(day) = grouping {
+                project_totals.insert(day, totals);
+            }
+        }
+    }
+    print_log(slices, options, &project_totals, all_slices, config)
+}
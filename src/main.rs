@@ -1,22 +1,41 @@
 // #[allow(unused_variables, unused_imports)]
-use chrono::{Duration, Utc};
+use chrono::{Datelike, Duration, Local, Utc};
 use clap::{App, AppSettings, Arg, SubCommand};
 use rusqlite::{Connection, Result, NO_PARAMS};
+use std::env;
 use std::error::Error;
 use std::path::{Path, PathBuf};
 // use std::result::Result;
-use xdg;
 
+mod absences;
+mod backup;
+mod balance;
+mod billing;
+mod budgets;
 mod colors;
+mod config;
 mod datetime;
 mod db;
+mod dbadmin;
 mod filter;
+mod goals;
+mod heatmap;
 mod import;
+mod interactive;
+mod invoice;
 mod log;
 mod migration;
+mod notes;
+mod profiles;
+mod projects;
+mod rates;
 mod schema;
+mod search;
 mod startstop;
+mod status;
 mod summarize;
+mod tags;
+mod timeline;
 mod tinylogger;
 
 fn get_default_db_filename() -> PathBuf {
@@ -24,21 +43,92 @@ fn get_default_db_filename() -> PathBuf {
     xdirs.place_data_file("punch.sqlite").unwrap()
 }
 
-fn get_db_filename<'a>(default_value: PathBuf, option_value: Option<&'a str>) -> PathBuf {
-    match option_value {
-        Some(v) => Path::new(v).to_path_buf(),
-        None => default_value,
+fn get_config_filename() -> PathBuf {
+    let xdirs = xdg::BaseDirectories::with_prefix("punch").unwrap();
+    xdirs.get_config_home().join("config.toml")
+}
+
+// an explicit file wins over a profile, which wins over the PUNCH_DB environment variable
+fn get_db_filename(
+    default_value: PathBuf,
+    option_value: Option<&str>,
+    profile: Option<&str>,
+) -> Result<PathBuf, Box<dyn Error>> {
+    match (option_value, profile) {
+        (Some(v), _) => Ok(Path::new(v).to_path_buf()),
+        (None, Some(name)) => profiles::get_profile_db_filename(name),
+        (None, None) => match env::var_os("PUNCH_DB") {
+            Some(v) => Ok(PathBuf::from(v)),
+            None => Ok(default_value),
+        },
     }
 }
 
-fn get_connection(filename: PathBuf) -> Result<Connection> {
-    let mut conn = Connection::open(filename)?;
+fn open_connection(filename: PathBuf) -> Result<Connection> {
+    let conn = Connection::open(filename)?;
     rusqlite::vtab::array::load_module(&conn)?;
     conn.execute("PRAGMA foreign_keys = ON;", NO_PARAMS)?;
+    Ok(conn)
+}
+
+fn get_connection(
+    filename: PathBuf,
+    config: &config::Config,
+) -> Result<Connection, Box<dyn Error>> {
+    let mut conn = open_connection(filename.clone())?;
+    migration::ensure_not_pinned(&conn, &schema::migrations())?;
+    backup::snapshot_before_migration(&conn, &filename, config)?;
     schema::migrate(&mut conn)?;
     Ok(conn)
 }
 
+fn rounding_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("round")
+            .help("round to this many minutes, e.g. 6 or 15. defaults to the rounding.minutes setting")
+            .long("round")
+            .takes_value(true)
+            .value_name("minutes"),
+        Arg::with_name("round-mode")
+            .help("how to round, defaults to the rounding.mode setting")
+            .long("round-mode")
+            .takes_value(true)
+            .possible_values(&["up", "down", "nearest"]),
+        Arg::with_name("round-per")
+            .help("round every slice or the time of each day, defaults to the rounding.per setting")
+            .long("round-per")
+            .takes_value(true)
+            .possible_values(&["slice", "day"]),
+    ]
+}
+
+// rounding options given on the command line win over the rounding config
+fn get_rounding(
+    matches: &clap::ArgMatches,
+    config: &config::RoundingConfig,
+) -> Result<Option<datetime::Rounding>, Box<dyn Error>> {
+    let minutes = match matches.value_of("round") {
+        Some(minutes) => minutes
+            .parse::<i64>()
+            .map_err(|_| format!("invalid number of minutes: {}", minutes))?,
+        None => config.minutes,
+    };
+    if minutes <= 0 {
+        return Ok(None);
+    }
+    Ok(Some(datetime::Rounding {
+        mode: matches
+            .value_of("round-mode")
+            .unwrap_or(&config.mode)
+            .parse()?,
+        granularity: Duration::minutes(minutes),
+        scope: matches
+            .value_of("round-per")
+            .unwrap_or(&config.per)
+            .parse()?,
+    }))
+}
+
 fn main() -> Result<(), Box<dyn Error>> {
     let matches = App::new("Punch")
         .setting(AppSettings::ArgRequiredElseHelp)
@@ -60,7 +150,92 @@ fn main() -> Result<(), Box<dyn Error>> {
                 .long("dbfile")
                 .takes_value(true)
                 .value_name("file")
-                .help("database file to use. defaults to ./punch.sqlite"),
+                .help("database file to use. defaults to $PUNCH_DB, the database setting or $XDG_DATA_HOME/punch/punch.sqlite"),
+        )
+        .arg(
+            Arg::with_name("profile")
+                .global(true)
+                .short("p")
+                .long("profile")
+                .takes_value(true)
+                .value_name("name")
+                .conflicts_with("dbfile")
+                .help("use the database of a named profile, e.g. work or personal"),
+        )
+        .arg(
+            Arg::with_name("duration-format")
+                .global(true)
+                .long("duration-format")
+                .takes_value(true)
+                .possible_values(&["hms", "decimal", "hh:mm", "compact", "seconds"])
+                .help("how durations are shown, defaults to the duration_format setting"),
+        )
+        .subcommand(SubCommand::with_name("profiles").about("list the available profiles"))
+        .subcommand(
+            SubCommand::with_name("config")
+                .about("show and change settings in the config file")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("get")
+                        .about("show the value of a setting")
+                        .arg(Arg::with_name("key").required(true).index(1)),
+                )
+                .subcommand(
+                    SubCommand::with_name("set")
+                        .about("change a setting, e.g. `punch config set log.range_days 14`")
+                        .arg(Arg::with_name("key").required(true).index(1))
+                        .arg(Arg::with_name("value").required(true).index(2)),
+                )
+                .subcommand(SubCommand::with_name("list").about("list all settings")),
+        )
+        .arg(
+            Arg::with_name("tz")
+                .global(true)
+                .long("tz")
+                .takes_value(true)
+                .value_name("zone")
+                .help("show times in the given IANA timezone, e.g. Europe/Zurich. defaults to the timezone each slice was recorded in"),
+        )
+        .subcommand(
+            SubCommand::with_name("backup")
+                .about("back up the database. without a path, a snapshot is written to the data directory")
+                .arg(
+                    Arg::with_name("path")
+                        .index(1)
+                        .help("file to write the backup to"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("restore-backup")
+                .about("replace the database with a backup")
+                .arg(
+                    Arg::with_name("path")
+                        .required(true)
+                        .index(1)
+                        .help("backup file to restore"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("db")
+                .about("inspect and migrate the database schema")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("version").about("show the schema version of the database"),
+                )
+                .subcommand(
+                    SubCommand::with_name("migrate")
+                        .about("migrate the database to the latest or a given schema version")
+                        .arg(
+                            Arg::with_name("to")
+                                .long("to")
+                                .takes_value(true)
+                                .value_name("N")
+                                .help("target schema version"),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("rollback").about("revert the most recent migration"),
+                ),
         )
         .subcommand(
             SubCommand::with_name("import")
@@ -74,12 +249,214 @@ fn main() -> Result<(), Box<dyn Error>> {
         )
         .subcommand(
             SubCommand::with_name("log")
-                .about("log slices created during the last 7d")
+                .about("log slices created during the last 7d, see the log.range_days setting")
                 .arg(
                     Arg::with_name("all")
                         .help("log all recorded slices")
                         .short("a")
                         .required(false),
+                )
+                .arg(
+                    Arg::with_name("project")
+                        .help("only log slices of this project and its sub-projects")
+                        .long("project")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("split")
+                        .help("split slices crossing midnight and show each part on its own day")
+                        .short("s")
+                        .long("split")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("no-split")
+                        .help("show slices crossing midnight whole, overriding the log.split setting")
+                        .long("no-split")
+                        .conflicts_with("split"),
+                )
+                .arg(
+                    Arg::with_name("breaks")
+                        .help("show the breaks between slices and the working hours of each day")
+                        .short("b")
+                        .long("breaks"),
+                )
+                .arg(
+                    Arg::with_name("no-breaks")
+                        .help("hide the breaks, overriding the log.breaks setting")
+                        .long("no-breaks")
+                        .conflicts_with("breaks"),
+                )
+                .arg(
+                    Arg::with_name("by-project")
+                        .help("show the time of each project below the total of a day")
+                        .long("by-project"),
+                )
+                .arg(
+                    Arg::with_name("check-breaks")
+                        .help("warn about days without the breaks required by the breaks.rules setting")
+                        .long("check-breaks"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("timeline")
+                .about("show a day or a week as bars of 24 hours colored by project")
+                .arg(
+                    Arg::with_name("week")
+                        .help("show the whole week")
+                        .short("w")
+                        .long("week"),
+                )
+                .arg(
+                    Arg::with_name("date")
+                        .help("day to show as YYYY-MM-DD, defaults to today")
+                        .long("date")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("heatmap")
+                .about("show the time of each day of a year, with weeks as columns and weekdays as rows")
+                .arg(
+                    Arg::with_name("year")
+                        .help("e.g. 2020, defaults to the current year")
+                        .long("year")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("project")
+                        .help("only count slices of this project and its sub-projects")
+                        .long("project")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("tui")
+                .about("browse and edit the slices of the last 7d full-screen, see the log.range_days setting")
+                .arg(
+                    Arg::with_name("all")
+                        .help("show all recorded slices")
+                        .short("a")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("project")
+                        .help("only show slices of this project and its sub-projects")
+                        .long("project")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("search")
+                .about("find slices by their note, project or tags")
+                .arg(
+                    Arg::with_name("query")
+                        .required(true)
+                        .multiple(true)
+                        .help("words to search for, supports the SQLite FTS5 query syntax"),
+                )
+                .arg(
+                    Arg::with_name("split")
+                        .help("split slices crossing midnight and show each part on its own day")
+                        .short("s")
+                        .long("split")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("no-split")
+                        .help("show slices crossing midnight whole, overriding the log.split setting")
+                        .long("no-split")
+                        .conflicts_with("split"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("projects")
+                .about("list and manage projects. lists projects if no subcommand is given")
+                .subcommand(
+                    SubCommand::with_name("list")
+                        .about("list projects with their total time and when they were last used")
+                        .arg(
+                            Arg::with_name("all")
+                                .help("include archived projects")
+                                .short("a")
+                                .long("all"),
+                        )
+                        .arg(
+                            Arg::with_name("names")
+                                .help("print project names only, e.g. for shell completion")
+                                .long("names"),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("rename")
+                        .about("rename a project")
+                        .arg(Arg::with_name("from").required(true).index(1))
+                        .arg(Arg::with_name("to").required(true).index(2)),
+                )
+                .subcommand(
+                    SubCommand::with_name("merge")
+                        .about("move all slices and tags of a project into another one and delete it")
+                        .arg(Arg::with_name("from").required(true).index(1))
+                        .arg(Arg::with_name("into").required(true).index(2)),
+                )
+                .subcommand(
+                    SubCommand::with_name("archive")
+                        .about("hide a project from listings and reports without deleting it")
+                        .arg(Arg::with_name("project").required(true).index(1))
+                        .arg(
+                            Arg::with_name("undo")
+                                .help("unarchive the project")
+                                .long("undo"),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("billable")
+                        .about("set whether new slices of a project are billable by default")
+                        .arg(Arg::with_name("project").required(true).index(1))
+                        .arg(
+                            Arg::with_name("no")
+                                .help("make new slices not billable")
+                                .long("no"),
+                        ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("tags")
+                .about("list and manage tags. lists tags if no subcommand is given")
+                .subcommand(
+                    SubCommand::with_name("list")
+                        .about("list tags with usage counts per project")
+                        .arg(
+                            Arg::with_name("project")
+                                .help("only list tags of this project")
+                                .index(1),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("rename")
+                        .about("rename a tag of a project")
+                        .arg(Arg::with_name("project").required(true).index(1))
+                        .arg(Arg::with_name("from").required(true).index(2))
+                        .arg(Arg::with_name("to").required(true).index(3)),
+                )
+                .subcommand(
+                    SubCommand::with_name("merge")
+                        .about("relink all slices of a tag to another tag of the same project and delete it")
+                        .arg(Arg::with_name("project").required(true).index(1))
+                        .arg(Arg::with_name("from").required(true).index(2))
+                        .arg(Arg::with_name("into").required(true).index(3)),
+                )
+                .subcommand(
+                    SubCommand::with_name("global")
+                        .about("make a tag global, shared by all projects. tags of the same name are merged into it")
+                        .arg(Arg::with_name("tag").required(true).index(1)),
+                )
+                .subcommand(
+                    SubCommand::with_name("move")
+                        .about("move a tag and the slices carrying it to another project")
+                        .arg(Arg::with_name("tag").required(true).index(1))
+                        .arg(Arg::with_name("from").required(true).index(2))
+                        .arg(Arg::with_name("to").required(true).index(3)),
                 ),
         )
         .subcommand(
@@ -96,9 +473,233 @@ fn main() -> Result<(), Box<dyn Error>> {
                         .multiple(true)
                         .takes_value(true)
                         .short("t"),
+                )
+                .arg(
+                    Arg::with_name("message")
+                        .help("note describing the work")
+                        .takes_value(true)
+                        .short("m")
+                        .long("message"),
+                )
+                .arg(
+                    Arg::with_name("billable")
+                        .help("mark the slice as billable, regardless of the project default")
+                        .long("billable")
+                        .conflicts_with("non-billable"),
+                )
+                .arg(
+                    Arg::with_name("non-billable")
+                        .help("mark the slice as not billable, regardless of the project default")
+                        .short("n")
+                        .long("non-billable"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("stop")
+                .about("stop currently running slice")
+                .arg(
+                    Arg::with_name("message")
+                        .help("line to append to the note of the slice")
+                        .takes_value(true)
+                        .short("m")
+                        .long("message"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("note")
+                .about("edit the note of a slice in $EDITOR, ids are shown by `log`")
+                .arg(Arg::with_name("id").required(true).help("slice id"))
+                .arg(
+                    Arg::with_name("message")
+                        .help("replace the note instead of opening an editor")
+                        .takes_value(true)
+                        .short("m")
+                        .long("message"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("rates")
+                .about("manage hourly rates used by `invoice`. lists rates if no subcommand is given")
+                .subcommand(SubCommand::with_name("list").about("list all rates"))
+                .subcommand(
+                    SubCommand::with_name("set")
+                        .about("set the hourly rate of a project and its sub-projects, or of a tag within them")
+                        .arg(Arg::with_name("project").required(true).index(1))
+                        .arg(Arg::with_name("rate").required(true).index(2).help("e.g. 95 or 95.50"))
+                        .arg(Arg::with_name("currency").required(true).index(3).help("e.g. EUR"))
+                        .arg(
+                            Arg::with_name("tag")
+                                .help("only apply the rate to slices with this tag")
+                                .long("tag")
+                                .takes_value(true),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("unset")
+                        .about("remove the hourly rate of a project or of a tag within it")
+                        .arg(Arg::with_name("project").required(true).index(1))
+                        .arg(Arg::with_name("tag").long("tag").takes_value(true)),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("budgets")
+                .about("manage project budgets. shows the consumed and remaining budgets if no subcommand is given")
+                .subcommand(SubCommand::with_name("list").about("list all budgets with their usage"))
+                .subcommand(
+                    SubCommand::with_name("set")
+                        .about("set the budget of a project and its sub-projects in hours, or in money if a currency is given")
+                        .arg(Arg::with_name("project").required(true).index(1))
+                        .arg(Arg::with_name("amount").required(true).index(2).help("hours such as 120, or an amount such as 5000.00"))
+                        .arg(Arg::with_name("currency").index(3).help("e.g. EUR, the budget is in hours without it")),
+                )
+                .subcommand(
+                    SubCommand::with_name("unset")
+                        .about("remove the budget of a project")
+                        .arg(Arg::with_name("project").required(true).index(1)),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("status")
+                .about("show the running slice and the usage of all budgets"),
+        )
+        .subcommand(
+            SubCommand::with_name("invoice")
+                .about("print the billable hours and amounts of a project for one month")
+                .arg(
+                    Arg::with_name("project")
+                        .help("project to invoice, including its sub-projects")
+                        .long("project")
+                        .takes_value(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("month")
+                        .help("month to invoice as YYYY-MM, defaults to the current month")
+                        .long("month")
+                        .takes_value(true),
+                )
+                .args(&rounding_args()),
+        )
+        .subcommand(
+            SubCommand::with_name("mark-billed")
+                .about("mark the billable slices of a month or date range as billed, locking them against changes")
+                .arg(
+                    Arg::with_name("project")
+                        .help("only mark slices of this project and its sub-projects")
+                        .long("project")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("month")
+                        .help("month as YYYY-MM")
+                        .long("month")
+                        .takes_value(true)
+                        .conflicts_with_all(&["from", "to"])
+                        .required_unless("from"),
+                )
+                .arg(
+                    Arg::with_name("from")
+                        .help("first day as YYYY-MM-DD")
+                        .long("from")
+                        .takes_value(true)
+                        .requires("to"),
+                )
+                .arg(
+                    Arg::with_name("to")
+                        .help("last day as YYYY-MM-DD")
+                        .long("to")
+                        .takes_value(true)
+                        .requires("from"),
+                )
+                .arg(
+                    Arg::with_name("invoice")
+                        .help("invoice reference to store with the slices, e.g. INV-2020-09")
+                        .long("invoice")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("undo")
+                        .help("unmark billed slices, only those of --invoice if given")
+                        .long("undo"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("goals")
+                .about("show the progress towards daily and weekly goals. lists goals if no subcommand is given")
+                .subcommand(
+                    SubCommand::with_name("set")
+                        .about("set the hours to work per day or week, on a project or on all projects")
+                        .arg(Arg::with_name("hours").required(true).index(1).help("e.g. 32 or 7.5"))
+                        .arg(
+                            Arg::with_name("per")
+                                .long("per")
+                                .takes_value(true)
+                                .possible_values(&["day", "week"])
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::with_name("project")
+                                .help("project the goal applies to, including its sub-projects")
+                                .long("project")
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::with_name("since")
+                                .help("first day of the goal as YYYY-MM-DD, defaults to today")
+                                .long("since")
+                                .takes_value(true),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("unset")
+                        .about("remove a goal")
+                        .arg(
+                            Arg::with_name("per")
+                                .long("per")
+                                .takes_value(true)
+                                .possible_values(&["day", "week"])
+                                .required(true),
+                        )
+                        .arg(Arg::with_name("project").long("project").takes_value(true)),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("absences")
+                .about("manage days off such as holidays and vacations. lists absences if no subcommand is given")
+                .subcommand(
+                    SubCommand::with_name("list")
+                        .about("list recorded absences")
+                        .arg(Arg::with_name("year").long("year").takes_value(true).help("e.g. 2020")),
+                )
+                .subcommand(
+                    SubCommand::with_name("add")
+                        .about("record a day or a range of days off")
+                        .arg(Arg::with_name("day").required(true).index(1).help("first day as YYYY-MM-DD"))
+                        .arg(Arg::with_name("to").long("to").takes_value(true).help("last day as YYYY-MM-DD"))
+                        .arg(
+                            Arg::with_name("reason")
+                                .long("reason")
+                                .takes_value(true)
+                                .help("e.g. vacation or holiday"),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("remove")
+                        .about("remove a day or a range of days off")
+                        .arg(Arg::with_name("day").required(true).index(1).help("first day as YYYY-MM-DD"))
+                        .arg(Arg::with_name("to").long("to").takes_value(true).help("last day as YYYY-MM-DD")),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("balance")
+                .about("show the overtime balance against the work schedule, with a breakdown per week")
+                .arg(
+                    Arg::with_name("from")
+                        .help("first day of the balance as YYYY-MM-DD, defaults to schedule.start or the first slice")
+                        .long("from")
+                        .takes_value(true),
                 ),
         )
-        .subcommand(SubCommand::with_name("stop").about("stop currently running slice"))
         .subcommand(
             SubCommand::with_name("summarize")
                 .about("Summarize work by project and time period. By default the output is grouped by day.")
@@ -107,18 +708,145 @@ fn main() -> Result<(), Box<dyn Error>> {
                         .help("Create only one group containing all work ever recorded")
                         .short("a")
                         .required(false),
+                )
+                .arg(
+                    Arg::with_name("day")
+                        .help("Group work by day")
+                        .short("d")
+                        .long("day")
+                        .conflicts_with("all")
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("week")
+                        .help("Group work by week")
+                        .short("w")
+                        .long("week")
+                        .conflicts_with_all(&["all", "day"])
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("month")
+                        .help("Group work by month")
+                        .short("m")
+                        .long("month")
+                        .conflicts_with_all(&["all", "day", "week"])
+                        .required(false),
+                )
+                .arg(
+                    Arg::with_name("project")
+                        .help("Only summarize this project and its sub-projects, e.g. acme for acme/website")
+                        .long("project")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("by")
+                        .help("Summarize by project, broken down by tag, or by tag, broken down by project")
+                        .long("by")
+                        .takes_value(true)
+                        .possible_values(&["project", "tag"])
+                        .default_value("project"),
+                )
+                .arg(
+                    Arg::with_name("no-split")
+                        .help("Count slices crossing a period boundary only in the period they stopped in")
+                        .long("no-split")
+                        .required(false),
+                )
+                .args(&rounding_args())
+                .arg(
+                    Arg::with_name("show-raw")
+                        .help("Show the unrounded time next to the rounded time")
+                        .long("show-raw"),
                 ),
         )
         .get_matches();
 
-    let db_filename = get_db_filename(get_default_db_filename(), matches.value_of("dbfile"));
-
     tinylogger::init(matches.is_present("verbose"))?;
 
+    let config_filename = get_config_filename();
+    if let Some(config_matches) = matches.subcommand_matches("config") {
+        if let Some(get_matches) = config_matches.subcommand_matches("get") {
+            config::get_command(&config_filename, get_matches.value_of("key").unwrap())?;
+        }
+        if let Some(set_matches) = config_matches.subcommand_matches("set") {
+            config::set_command(
+                &config_filename,
+                set_matches.value_of("key").unwrap(),
+                set_matches.value_of("value").unwrap(),
+            )?;
+        }
+        if config_matches.subcommand_matches("list").is_some() {
+            config::list_command(&config_filename)?;
+        }
+        return Ok(());
+    }
+    let mut config = config::load(&config_filename)?;
+    if let Some(duration_format) = matches.value_of("duration-format") {
+        config.duration_format = duration_format.to_string();
+    }
+    let config = &config;
+
+    let default_db_filename = match &config.database {
+        Some(database) => database.clone(),
+        None => get_default_db_filename(),
+    };
+    let db_filename = get_db_filename(
+        default_db_filename,
+        matches.value_of("dbfile"),
+        matches.value_of("profile"),
+    )?;
+
+    if matches.subcommand_matches("profiles").is_some() {
+        profiles::profiles_command(&db_filename, config)?;
+    }
+
+    let tz = match matches.value_of("tz") {
+        Some(name) => datetime::parse_display_timezone(name)?,
+        None => datetime::DisplayTimezone::Recorded,
+    };
+
+    if let Some(backup_matches) = matches.subcommand_matches("backup") {
+        backup::backup_command(
+            &open_connection(db_filename.clone())?,
+            &db_filename,
+            backup_matches.value_of("path"),
+            config,
+        )?;
+    }
+
+    if let Some(restore_matches) = matches.subcommand_matches("restore-backup") {
+        if let Some(path) = restore_matches.value_of("path") {
+            backup::restore_backup_command(
+                &mut open_connection(db_filename.clone())?,
+                &db_filename,
+                path,
+                config,
+            )?;
+        }
+    }
+
+    if let Some(db_matches) = matches.subcommand_matches("db") {
+        let mut conn = open_connection(db_filename.clone())?;
+        if db_matches.subcommand_matches("version").is_some() {
+            dbadmin::version_command(&conn, config)?;
+        }
+        if let Some(migrate_matches) = db_matches.subcommand_matches("migrate") {
+            let target = match migrate_matches.value_of("to") {
+                Some(to) => Some(to.parse::<u64>()?),
+                None => None,
+            };
+            dbadmin::migrate_command(&mut conn, &db_filename, target, config)?;
+        }
+        if db_matches.subcommand_matches("rollback").is_some() {
+            dbadmin::rollback_command(&mut conn, &db_filename, config)?;
+        }
+    }
+
     if let Some(import_matches) = matches.subcommand_matches("import") {
         if let Some(import_file) = import_matches.value_of("file") {
             println!("importing from file: {}", import_file);
-            let mut conn = get_connection(db_filename.clone())?;
+            let mut conn = get_connection(db_filename.clone(), config)?;
             import::import_watson_frames(&mut conn, import_file)?;
         }
     }
@@ -127,12 +855,163 @@ fn main() -> Result<(), Box<dyn Error>> {
         let from = Some(if log_matches.is_present("all") {
             datetime::timestamp_1970()
         } else {
-            Utc::now() - Duration::days(7)
+            Utc::now() - Duration::days(config.log.range_days)
+        });
+
+        let filter = filter::Filter {
+            from,
+            to: None,
+            project: log_matches.value_of("project").map(String::from),
+            archived: false,
+        };
+
+        log::log_command(
+            &mut get_connection(db_filename.clone(), config)?,
+            &filter,
+            &log::LogOptions {
+                split: (log_matches.is_present("split") || config.log.split)
+                    && !log_matches.is_present("no-split"),
+                breaks: (log_matches.is_present("breaks") || config.log.breaks)
+                    && !log_matches.is_present("no-breaks"),
+                check_breaks: log_matches.is_present("check-breaks"),
+                by_project: log_matches.is_present("by-project"),
+            },
+            tz,
+            config,
+        )?;
+    }
+
+    if let Some(timeline_matches) = matches.subcommand_matches("timeline") {
+        timeline::timeline_command(
+            &get_connection(db_filename.clone(), config)?,
+            match timeline_matches.value_of("date") {
+                Some(date) => datetime::parse_date(date)?,
+                None => datetime::today(tz),
+            },
+            timeline_matches.is_present("week"),
+            tz,
+            config,
+        )?;
+    }
+
+    if let Some(heatmap_matches) = matches.subcommand_matches("heatmap") {
+        heatmap::heatmap_command(
+            &get_connection(db_filename.clone(), config)?,
+            match heatmap_matches.value_of("year") {
+                Some(year) => datetime::parse_year(year)?,
+                None => datetime::today(tz).year(),
+            },
+            heatmap_matches.value_of("project"),
+            tz,
+            config,
+        )?;
+    }
+
+    if let Some(tui_matches) = matches.subcommand_matches("tui") {
+        let from = Some(if tui_matches.is_present("all") {
+            datetime::timestamp_1970()
+        } else {
+            Utc::now() - Duration::days(config.log.range_days)
         });
 
-        let filter = filter::Filter { from, to: None };
+        interactive::interactive_command(
+            &mut get_connection(db_filename.clone(), config)?,
+            &filter::Filter {
+                from,
+                to: None,
+                project: tui_matches.value_of("project").map(String::from),
+                archived: false,
+            },
+            tz,
+            config,
+        )?;
+    }
 
-        log::log_command(&mut get_connection(db_filename.clone())?, &filter)?;
+    if let Some(search_matches) = matches.subcommand_matches("search") {
+        let query = search_matches
+            .values_of("query")
+            .unwrap()
+            .collect::<Vec<&str>>()
+            .join(" ");
+        search::search_command(
+            &mut get_connection(db_filename.clone(), config)?,
+            &query,
+            (search_matches.is_present("split") || config.log.split)
+                && !search_matches.is_present("no-split"),
+            tz,
+            config,
+        )?;
+    }
+
+    if let Some(projects_matches) = matches.subcommand_matches("projects") {
+        let mut conn = get_connection(db_filename.clone(), config)?;
+        match projects_matches.subcommand() {
+            ("rename", Some(rename_matches)) => projects::rename_command(
+                &mut conn,
+                rename_matches.value_of("from").unwrap(),
+                rename_matches.value_of("to").unwrap(),
+                config,
+            )?,
+            ("merge", Some(merge_matches)) => projects::merge_command(
+                &mut conn,
+                merge_matches.value_of("from").unwrap(),
+                merge_matches.value_of("into").unwrap(),
+                config,
+            )?,
+            ("archive", Some(archive_matches)) => projects::archive_command(
+                &mut conn,
+                archive_matches.value_of("project").unwrap(),
+                !archive_matches.is_present("undo"),
+                config,
+            )?,
+            ("billable", Some(billable_matches)) => billing::billable_command(
+                &mut conn,
+                billable_matches.value_of("project").unwrap(),
+                !billable_matches.is_present("no"),
+                config,
+            )?,
+            ("list", Some(list_matches)) => projects::list_command(
+                &conn,
+                list_matches.is_present("all"),
+                list_matches.is_present("names"),
+                config,
+            )?,
+            _ => projects::list_command(&conn, false, false, config)?,
+        }
+    }
+
+    if let Some(tags_matches) = matches.subcommand_matches("tags") {
+        let mut conn = get_connection(db_filename.clone(), config)?;
+        match tags_matches.subcommand() {
+            ("rename", Some(rename_matches)) => tags::rename_command(
+                &mut conn,
+                rename_matches.value_of("project").unwrap(),
+                rename_matches.value_of("from").unwrap(),
+                rename_matches.value_of("to").unwrap(),
+                config,
+            )?,
+            ("merge", Some(merge_matches)) => tags::merge_command(
+                &mut conn,
+                merge_matches.value_of("project").unwrap(),
+                merge_matches.value_of("from").unwrap(),
+                merge_matches.value_of("into").unwrap(),
+                config,
+            )?,
+            ("move", Some(move_matches)) => tags::move_command(
+                &mut conn,
+                move_matches.value_of("tag").unwrap(),
+                move_matches.value_of("from").unwrap(),
+                move_matches.value_of("to").unwrap(),
+                config,
+            )?,
+            ("global", Some(global_matches)) => {
+                tags::global_command(&mut conn, global_matches.value_of("tag").unwrap(), config)?
+            }
+            ("list", Some(list_matches)) => {
+                tags::list_command(&conn, list_matches.value_of("project"), config)?
+            }
+            _ => tags::list_command(&conn, None, config)?,
+        }
     }
 
     if let Some(start_matches) = matches.subcommand_matches("start") {
@@ -142,24 +1021,231 @@ fn main() -> Result<(), Box<dyn Error>> {
                 None => vec![],
             };
             startstop::start_command(
-                &mut get_connection(db_filename.clone())?,
+                &mut get_connection(db_filename.clone(), config)?,
                 project_name,
                 &tags,
+                start_matches.value_of("message"),
+                if start_matches.is_present("billable") {
+                    Some(true)
+                } else if start_matches.is_present("non-billable") {
+                    Some(false)
+                } else {
+                    None
+                },
+                config,
             )?;
         }
     }
 
-    if let Some(_args) = matches.subcommand_matches("stop") {
-        startstop::stop_command(&mut get_connection(db_filename.clone())?)?;
+    if let Some(stop_matches) = matches.subcommand_matches("stop") {
+        startstop::stop_command(
+            &mut get_connection(db_filename.clone(), config)?,
+            stop_matches.value_of("message"),
+        )?;
+    }
+
+    if let Some(note_matches) = matches.subcommand_matches("note") {
+        let id = note_matches
+            .value_of("id")
+            .unwrap()
+            .trim_start_matches('#')
+            .parse::<i64>()
+            .map_err(|_| "slice id must be a number")?;
+        notes::note_command(
+            &mut get_connection(db_filename.clone(), config)?,
+            id,
+            note_matches.value_of("message"),
+        )?;
+    }
+
+    if let Some(rates_matches) = matches.subcommand_matches("rates") {
+        let mut conn = get_connection(db_filename.clone(), config)?;
+        match rates_matches.subcommand() {
+            ("set", Some(set_matches)) => rates::set_command(
+                &mut conn,
+                set_matches.value_of("project").unwrap(),
+                set_matches.value_of("tag"),
+                set_matches.value_of("rate").unwrap(),
+                set_matches.value_of("currency").unwrap(),
+                config,
+            )?,
+            ("unset", Some(unset_matches)) => rates::unset_command(
+                &mut conn,
+                unset_matches.value_of("project").unwrap(),
+                unset_matches.value_of("tag"),
+                config,
+            )?,
+            _ => rates::list_command(&conn, config)?,
+        }
+    }
+
+    if let Some(budgets_matches) = matches.subcommand_matches("budgets") {
+        let mut conn = get_connection(db_filename.clone(), config)?;
+        match budgets_matches.subcommand() {
+            ("set", Some(set_matches)) => budgets::set_command(
+                &mut conn,
+                set_matches.value_of("project").unwrap(),
+                set_matches.value_of("amount").unwrap(),
+                set_matches.value_of("currency"),
+                config,
+            )?,
+            ("unset", Some(unset_matches)) => budgets::unset_command(
+                &mut conn,
+                unset_matches.value_of("project").unwrap(),
+                config,
+            )?,
+            _ => budgets::list_command(&conn, config)?,
+        }
+    }
+
+    if matches.subcommand_matches("status").is_some() {
+        status::status_command(&get_connection(db_filename.clone(), config)?, config)?;
+    }
+
+    if let Some(invoice_matches) = matches.subcommand_matches("invoice") {
+        let month = match invoice_matches.value_of("month") {
+            Some(month) => datetime::parse_month(month)?,
+            None => datetime::parse_month(&Local::now().format("%Y-%m").to_string())?,
+        };
+        invoice::invoice_command(
+            &get_connection(db_filename.clone(), config)?,
+            invoice_matches.value_of("project").unwrap(),
+            month,
+            get_rounding(invoice_matches, &config.rounding)?,
+            tz,
+            config,
+        )?;
+    }
+
+    if let Some(mark_billed_matches) = matches.subcommand_matches("mark-billed") {
+        let (from, to) = match mark_billed_matches.value_of("month") {
+            Some(month) => {
+                let month = datetime::parse_month(month)?;
+                (month, datetime::next_month(month).pred())
+            }
+            None => (
+                datetime::parse_date(mark_billed_matches.value_of("from").unwrap())?,
+                datetime::parse_date(mark_billed_matches.value_of("to").unwrap())?,
+            ),
+        };
+        billing::mark_billed_command(
+            &mut get_connection(db_filename.clone(), config)?,
+            mark_billed_matches.value_of("project"),
+            from,
+            to,
+            mark_billed_matches.value_of("invoice"),
+            mark_billed_matches.is_present("undo"),
+            tz,
+            config,
+        )?;
+    }
+
+    if let Some(goals_matches) = matches.subcommand_matches("goals") {
+        let mut conn = get_connection(db_filename.clone(), config)?;
+        match goals_matches.subcommand() {
+            ("set", Some(set_matches)) => goals::set_command(
+                &mut conn,
+                set_matches.value_of("project"),
+                set_matches.value_of("per").unwrap(),
+                set_matches.value_of("hours").unwrap(),
+                match set_matches.value_of("since") {
+                    Some(since) => Some(datetime::parse_date(since)?),
+                    None => None,
+                },
+                tz,
+                config,
+            )?,
+            ("unset", Some(unset_matches)) => goals::unset_command(
+                &mut conn,
+                unset_matches.value_of("project"),
+                unset_matches.value_of("per").unwrap(),
+                config,
+            )?,
+            _ => goals::goals_command(&conn, tz, config)?,
+        }
+    }
+
+    if let Some(absences_matches) = matches.subcommand_matches("absences") {
+        let mut conn = get_connection(db_filename.clone(), config)?;
+        match absences_matches.subcommand() {
+            ("add", Some(add_matches)) => absences::add_command(
+                &mut conn,
+                datetime::parse_date(add_matches.value_of("day").unwrap())?,
+                match add_matches.value_of("to") {
+                    Some(to) => Some(datetime::parse_date(to)?),
+                    None => None,
+                },
+                add_matches.value_of("reason"),
+            )?,
+            ("remove", Some(remove_matches)) => absences::remove_command(
+                &mut conn,
+                datetime::parse_date(remove_matches.value_of("day").unwrap())?,
+                match remove_matches.value_of("to") {
+                    Some(to) => Some(datetime::parse_date(to)?),
+                    None => None,
+                },
+            )?,
+            ("list", Some(list_matches)) => absences::list_command(
+                &conn,
+                match list_matches.value_of("year") {
+                    Some(year) => Some(datetime::parse_year(year)?),
+                    None => None,
+                },
+                config,
+            )?,
+            _ => absences::list_command(&conn, None, config)?,
+        }
+    }
+
+    if let Some(balance_matches) = matches.subcommand_matches("balance") {
+        balance::balance_command(
+            &get_connection(db_filename.clone(), config)?,
+            match balance_matches.value_of("from") {
+                Some(from) => Some(datetime::parse_date(from)?),
+                None => None,
+            },
+            tz,
+            config,
+        )?;
     }
 
     if let Some(summarize_matches) = matches.subcommand_matches("summarize") {
         let grouping_mode = if summarize_matches.is_present("all") {
             summarize::GroupingMode::All
-        } else {
+        } else if summarize_matches.is_present("week") {
+            summarize::GroupingMode::Week
+        } else if summarize_matches.is_present("month") {
+            summarize::GroupingMode::Month
+        } else if summarize_matches.is_present("day") {
             summarize::GroupingMode::Day
+        } else {
+            config
+                .summarize
+                .grouping
+                .parse::<summarize::GroupingMode>()?
+        };
+        let filter = filter::Filter {
+            from: None,
+            to: None,
+            project: summarize_matches.value_of("project").map(String::from),
+            archived: false,
         };
-        summarize::summarize_command(&mut get_connection(db_filename.clone())?, grouping_mode)?;
+        summarize::summarize_command(
+            &mut get_connection(db_filename.clone(), config)?,
+            &filter,
+            summarize::SummarizeOptions {
+                grouping_mode,
+                dimension: summarize_matches
+                    .value_of("by")
+                    .unwrap()
+                    .parse::<summarize::SummaryDimension>()?,
+                split: config.summarize.split && !summarize_matches.is_present("no-split"),
+                rounding: get_rounding(summarize_matches, &config.rounding)?,
+                show_raw: summarize_matches.is_present("show-raw") || config.rounding.show_raw,
+            },
+            tz,
+            config,
+        )?;
     }
 
     Ok(())
@@ -1,11 +1,13 @@
 use log::debug;
 use rusqlite::{Connection, OptionalExtension, Result, NO_PARAMS};
+use std::error::Error;
 
 type MigrationFunction = fn(&Connection) -> Result<bool>;
 
 pub struct Migration {
     pub id: u64,
     pub migration_fn: MigrationFunction,
+    pub down_fn: Option<MigrationFunction>,
 }
 
 pub fn create_schema_migrations_table(conn: &Connection) -> Result<usize> {
@@ -33,27 +35,205 @@ fn has_migration(conn: &Connection, id: u64) -> Result<bool> {
     }
 }
 
-fn execute_migration(conn: &mut Connection, migration: Migration) -> Result<()> {
+/// Returns the id of the most recent migration applied to the database, 0 if there is none.
+pub fn current_version(conn: &Connection) -> Result<u64> {
+    create_schema_migrations_table(conn)?;
+    let version: Option<i64> =
+        conn.query_row("SELECT max(id) FROM schema_migrations", NO_PARAMS, |row| {
+            row.get(0)
+        })?;
+    Ok(version.unwrap_or(0) as u64)
+}
+
+// `db migrate --to` and `db rollback` pin the database to the version they leave it at, so the
+// next command does not silently migrate it forward again
+fn create_schema_pin_table(conn: &Connection) -> Result<usize> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS schema_pin(
+        version INTEGER NOT NULL
+      );",
+        NO_PARAMS,
+    )
+}
+
+/// Returns the version the database was deliberately migrated down to, if any.
+pub fn pinned_version(conn: &Connection) -> Result<Option<u64>> {
+    create_schema_pin_table(conn)?;
+    let version: Option<i64> = conn
+        .query_row("SELECT version FROM schema_pin", NO_PARAMS, |row| {
+            row.get(0)
+        })
+        .optional()?;
+    Ok(version.map(|v| v as u64))
+}
+
+/// Pins the database to `version`, or removes the pin.
+pub fn pin_version(conn: &Connection, version: Option<u64>) -> Result<()> {
+    create_schema_pin_table(conn)?;
+    conn.execute("DELETE FROM schema_pin", NO_PARAMS)?;
+    if let Some(version) = version {
+        conn.execute(
+            "INSERT INTO schema_pin (version) VALUES (?)",
+            [version as i64],
+        )?;
+    }
+    Ok(())
+}
+
+/// Fails if the database has pending migrations but was pinned to its version.
+pub fn ensure_not_pinned(
+    conn: &Connection,
+    migrations: &[Migration],
+) -> Result<(), Box<dyn Error>> {
+    if let Some(version) = pinned_version(conn)? {
+        if current_version(conn)? < latest_version(migrations) {
+            return Err(format!(
+                "database is pinned to schema version {} by `punch db migrate --to` or `punch db rollback`, run `punch db migrate` to upgrade it",
+                version
+            )
+            .into());
+        }
+    }
+    Ok(())
+}
+
+/// Returns the id of the most recent migration known to this binary.
+pub fn latest_version(migrations: &[Migration]) -> u64 {
+    migrations.iter().map(|m| m.id).max().unwrap_or(0)
+}
+
+fn check_foreign_keys(conn: &Connection) -> Result<(), Box<dyn Error>> {
+    let violations: i64 = conn.query_row(
+        "SELECT count(*) FROM pragma_foreign_key_check",
+        NO_PARAMS,
+        |row| row.get(0),
+    )?;
+    if violations > 0 {
+        return Err(format!("migration left {} foreign key violation(s)", violations).into());
+    }
+    Ok(())
+}
+
+// Foreign keys are switched off while a migration runs so tables can be rebuilt, which SQLite
+// requires for most column changes. Violations are checked before the transaction commits.
+fn run_migration_fn(
+    conn: &mut Connection,
+    migration_fn: MigrationFunction,
+    bookkeeping: &str,
+    id: u64,
+) -> Result<(), Box<dyn Error>> {
+    conn.execute("PRAGMA foreign_keys = OFF;", NO_PARAMS)?;
+    let result = (|| -> Result<(), Box<dyn Error>> {
+        let tx = conn.transaction()?;
+        migration_fn(&tx)?;
+        check_foreign_keys(&tx)?;
+        tx.execute(bookkeeping, &[id.to_string()])?;
+        tx.commit()?;
+        Ok(())
+    })();
+    conn.execute("PRAGMA foreign_keys = ON;", NO_PARAMS)?;
+    result
+}
+
+fn execute_migration(conn: &mut Connection, migration: &Migration) -> Result<(), Box<dyn Error>> {
     if !has_migration(conn, migration.id)? {
         debug!("applying migration #{}", migration.id);
-        let tx = conn.transaction()?;
-        (migration.migration_fn)(&tx)?;
-        tx.execute(
+        run_migration_fn(
+            conn,
+            migration.migration_fn,
             "INSERT INTO schema_migrations (id, executed_on) VALUES (?, datetime('now'))",
-            &[migration.id.to_string()],
+            migration.id,
         )?;
-        tx.commit()?;
     }
     Ok(())
 }
 
-pub fn execute_migrations(conn: &mut Connection, migrations: Vec<Migration>) -> Result<()> {
-    create_schema_migrations_table(&conn)?;
+fn revert_migration(conn: &mut Connection, migration: &Migration) -> Result<(), Box<dyn Error>> {
+    if has_migration(conn, migration.id)? {
+        let down_fn = migration
+            .down_fn
+            .ok_or(format!("migration #{} cannot be reverted", migration.id))?;
+        debug!("reverting migration #{}", migration.id);
+        run_migration_fn(
+            conn,
+            down_fn,
+            "DELETE FROM schema_migrations WHERE id = ?",
+            migration.id,
+        )?;
+    }
+    Ok(())
+}
+
+/// Fails if the database has been migrated by a newer version of punch.
+pub fn ensure_supported_version(
+    conn: &Connection,
+    migrations: &[Migration],
+) -> Result<(), Box<dyn Error>> {
+    let current = current_version(conn)?;
+    let latest = latest_version(migrations);
+    if current > latest {
+        return Err(format!(
+            "database schema version {} is newer than the latest version {} supported by this binary",
+            current, latest
+        )
+        .into());
+    }
+    Ok(())
+}
+
+pub fn execute_migrations(
+    conn: &mut Connection,
+    migrations: Vec<Migration>,
+) -> Result<(), Box<dyn Error>> {
+    create_schema_migrations_table(conn)?;
+    ensure_supported_version(conn, &migrations)?;
+    ensure_not_pinned(conn, &migrations)?;
 
     migrations
-        .into_iter()
+        .iter()
         .map(|m| execute_migration(conn, m))
-        .collect::<Result<Vec<_>>>()?;
+        .collect::<Result<Vec<_>, _>>()?;
 
     Ok(())
 }
+
+/// Migrates the database up or down until `target` is the most recent applied migration.
+pub fn migrate_to(
+    conn: &mut Connection,
+    mut migrations: Vec<Migration>,
+    target: u64,
+) -> Result<(), Box<dyn Error>> {
+    create_schema_migrations_table(conn)?;
+    ensure_supported_version(conn, &migrations)?;
+    if target > latest_version(&migrations) {
+        return Err(format!("unknown schema version {}", target).into());
+    }
+
+    migrations.sort_by_key(|m| m.id);
+    for migration in migrations.iter().filter(|m| m.id <= target) {
+        execute_migration(conn, migration)?;
+    }
+    for migration in migrations.iter().rev().filter(|m| m.id > target) {
+        revert_migration(conn, migration)?;
+    }
+    let latest = latest_version(&migrations);
+    pin_version(conn, if target < latest { Some(target) } else { None })?;
+
+    Ok(())
+}
+
+/// Reverts the most recent migration, returning the version the database was rolled back to.
+pub fn rollback(conn: &mut Connection, migrations: Vec<Migration>) -> Result<u64, Box<dyn Error>> {
+    let current = current_version(conn)?;
+    if current == 0 {
+        return Err("no migrations to roll back".into());
+    }
+    let target = migrations
+        .iter()
+        .map(|m| m.id)
+        .filter(|id| *id < current)
+        .max()
+        .unwrap_or(0);
+    migrate_to(conn, migrations, target)?;
+    Ok(target)
+}
@@ -0,0 +1,68 @@
+use rusqlite::Connection;
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::io::Write;
+use std::process;
+use std::result::Result;
+
+use crate::billing;
+use crate::db;
+
+// opens the note in $VISUAL or $EDITOR and returns the edited text
+fn edit_in_editor(timeslice_id: i64, note: &str) -> Result<String, Box<dyn Error>> {
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .map_err(|_| "set $EDITOR or use -m to give the note on the command line")?;
+    // created exclusively and only readable by the user, removed again when dropped
+    let mut file = tempfile::Builder::new()
+        .prefix(&format!("punch-note-{}-", timeslice_id))
+        .suffix(".txt")
+        .tempfile()?;
+    file.write_all(note.as_bytes())?;
+    file.flush()?;
+    let path = file.path().to_path_buf();
+    // the editor may be given with arguments such as `code --wait`
+    let status = process::Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$1\"", editor))
+        .arg("sh")
+        .arg(&path)
+        .status()?;
+    let edited = fs::read_to_string(&path);
+    file.close()?;
+    if !status.success() {
+        return Err(format!("editor exited with {}, note not changed", status).into());
+    }
+    Ok(edited?)
+}
+
+pub fn note_command(
+    conn: &mut Connection,
+    timeslice_id: i64,
+    message: Option<&str>,
+) -> Result<(), Box<dyn Error>> {
+    // no transaction is held while the editor is open, it would block other commands
+    let current = db::timeslice_get_note(conn, timeslice_id)?
+        .ok_or_else(|| format!("slice {} not found", timeslice_id))?;
+    billing::ensure_slice_unlocked(conn, timeslice_id)?;
+    let note = match message {
+        Some(message) => message.to_string(),
+        None => edit_in_editor(timeslice_id, current.as_deref().unwrap_or(""))?,
+    };
+    let note = note.trim();
+
+    // the slice may have been deleted or billed in the meantime
+    let tx = conn.transaction()?;
+    db::timeslice_get_note(&tx, timeslice_id)?
+        .ok_or_else(|| format!("slice {} not found", timeslice_id))?;
+    billing::ensure_slice_unlocked(&tx, timeslice_id)?;
+    db::timeslice_set_note(&tx, timeslice_id, Some(note).filter(|n| !n.is_empty()))?;
+    tx.commit()?;
+    if note.is_empty() {
+        println!("removed note of slice {}", timeslice_id);
+    } else {
+        println!("updated note of slice {}", timeslice_id);
+    }
+    Ok(())
+}
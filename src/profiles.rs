@@ -0,0 +1,59 @@
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::result::Result;
+
+use crate::colors::Colors;
+use crate::config::Config;
+
+fn get_profiles_dir() -> Result<PathBuf, Box<dyn Error>> {
+    let xdirs = xdg::BaseDirectories::with_prefix("punch")?;
+    Ok(xdirs.create_data_directory("profiles")?)
+}
+
+/// Returns the database file of the named profile in the XDG data directory.
+pub fn get_profile_db_filename(name: &str) -> Result<PathBuf, Box<dyn Error>> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(format!(
+            "invalid profile name {}, use letters, digits, '-' and '_' only",
+            name
+        )
+        .into());
+    }
+    Ok(get_profiles_dir()?.join(format!("{}.sqlite", name)))
+}
+
+/// Returns the name of the profile `db_filename` belongs to, if it is a profile database.
+pub fn profile_name(db_filename: &Path) -> Result<Option<String>, Box<dyn Error>> {
+    if db_filename.parent() != Some(get_profiles_dir()?.as_path()) {
+        return Ok(None);
+    }
+    Ok(db_filename
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string()))
+}
+
+pub fn profiles_command(active_db_filename: &Path, config: &Config) -> Result<(), Box<dyn Error>> {
+    let mut profiles = fs::read_dir(get_profiles_dir()?)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().map(|e| e == "sqlite").unwrap_or(false))
+        .collect::<Vec<PathBuf>>();
+    profiles.sort();
+
+    if profiles.is_empty() {
+        println!("No profiles found. Use --profile <name> to create one.");
+    }
+    for path in profiles {
+        let name = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let marker = if path == active_db_filename { "*" } else { " " };
+        println!("{} {}", marker, name.as_str().color_project(&config.colors));
+    }
+    Ok(())
+}
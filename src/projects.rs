@@ -0,0 +1,256 @@
+use chrono::Duration;
+use rusqlite::{named_params, params, Connection};
+use std::error::Error;
+use std::result::Result;
+
+use crate::billing;
+use crate::colors::Colors;
+use crate::config::Config;
+use crate::datetime;
+use crate::db;
+use crate::filter;
+
+/// Separates the levels of a project path such as `acme/website/backend`.
+pub const PROJECT_SEPARATOR: char = '/';
+
+/// Returns the paths of all ancestors of a project and the project itself, starting at the root.
+pub fn project_ancestors(title: &str) -> Vec<&str> {
+    title
+        .match_indices(PROJECT_SEPARATOR)
+        .map(|(i, _)| &title[..i])
+        .chain(std::iter::once(title))
+        .collect()
+}
+
+#[derive(Debug)]
+struct ProjectListRow {
+    title: String,
+    archived: bool,
+    total_time: Duration,
+    last_used: Option<String>,
+}
+
+pub fn get_project(conn: &Connection, name: &str) -> Result<db::Project, Box<dyn Error>> {
+    db::project_get_by_name(conn, name)?.ok_or_else(|| format!("project {} not found", name).into())
+}
+
+/// Like `get_project`, but also accepts the parent of a nested project, which only gets a row of
+/// its own once settings such as rates are attached to it.
+pub fn get_project_or_parent(conn: &Connection, name: &str) -> Result<db::Project, Box<dyn Error>> {
+    if db::project_get_by_name(conn, name)?.is_none() {
+        let is_parent = conn.query_row_named(
+            &format!(
+                "SELECT EXISTS(SELECT 1 FROM project WHERE {})",
+                filter::PROJECT_CONDITION
+            ),
+            named_params! {":filter_project": name},
+            |row| row.get::<_, bool>(0),
+        )?;
+        if is_parent {
+            db::project_create(conn, name)?;
+        }
+    }
+    get_project(conn, name)
+}
+
+pub fn list_command(
+    conn: &Connection,
+    include_archived: bool,
+    names_only: bool,
+    config: &Config,
+) -> Result<(), Box<dyn Error>> {
+    let mut stmt = conn.prepare(
+        "
+        SELECT
+            project.title,
+            project.archived,
+            CAST(
+                total((strftime('%J',stopped_on) - strftime('%J',started_on)) * 24 * 3600)
+                AS INTEGER
+            ) AS duration,
+            max(started_on) AS last_used
+        FROM project
+        LEFT JOIN timeslice USING(project_id)
+        WHERE ?1 OR NOT project.archived
+        GROUP BY project.project_id
+        ORDER BY last_used DESC
+    ",
+    )?;
+
+    let rows = stmt
+        .query_map(params![include_archived], |row| {
+            Ok(ProjectListRow {
+                title: row.get(0)?,
+                archived: row.get(1)?,
+                total_time: Duration::seconds(row.get(2)?),
+                last_used: row.get(3)?,
+            })
+        })?
+        .map(|row| row.unwrap())
+        .collect::<Vec<ProjectListRow>>();
+
+    for row in rows {
+        if names_only {
+            println!("{}", row.title);
+            continue;
+        }
+        let last_used = match &row.last_used {
+            Some(started_on) => datetime::naivedate_format(
+                datetime::as_local(datetime::from_rfc3339_string(started_on))
+                    .naive_local()
+                    .date(),
+                config,
+            ),
+            None => String::from("never"),
+        };
+        println!(
+            "{project_title:<20} {duration:>14}  {last_used}{archived}",
+            project_title = row.title.as_str().color_project(&config.colors),
+            duration =
+                datetime::format_duration(&row.total_time, config)?.color_duration(&config.colors),
+            last_used = last_used.as_str().color_time(&config.colors),
+            archived = if row.archived { "  (archived)" } else { "" }
+        );
+    }
+    Ok(())
+}
+
+// the project and its sub-projects at all levels, the parent of nested projects gets a row of
+// its own if it has none yet
+fn get_project_tree(conn: &Connection, name: &str) -> Result<Vec<db::Project>, Box<dyn Error>> {
+    let mut tree = vec![get_project_or_parent(conn, name)?];
+    tree.extend(db::project_list_descendants(conn, name)?);
+    Ok(tree)
+}
+
+// the path of `title` within the tree of `from`, moved to the tree of `to`
+fn moved_title(title: &str, from: &str, to: &str) -> String {
+    format!("{}{}", to, &title[from.len()..])
+}
+
+// the end of the messages of the commands below, which act on a project and its sub-projects
+fn with_sub_projects(tree: &[db::Project]) -> String {
+    match tree.len() - 1 {
+        0 => String::new(),
+        1 => String::from(", with 1 sub-project"),
+        n => format!(", with {} sub-projects", n),
+    }
+}
+
+/// Renames a project together with its sub-projects.
+pub fn rename_command(
+    conn: &mut Connection,
+    from: &str,
+    to: &str,
+    config: &Config,
+) -> Result<(), Box<dyn Error>> {
+    let tx = conn.transaction()?;
+    let tree = get_project_tree(&tx, from)?;
+    for project in &tree {
+        billing::ensure_project_unlocked(&tx, project)?;
+        let title = moved_title(&project.title, from, to);
+        if db::project_get_by_name(&tx, &title)?.is_some() {
+            return Err(format!(
+                "project {} already exists, use `punch projects merge {} {}` to combine them",
+                title, from, to
+            )
+            .into());
+        }
+    }
+    for project in &tree {
+        db::project_rename(&tx, project.id, &moved_title(&project.title, from, to))?;
+    }
+    tx.commit()?;
+    println!(
+        "renamed project {} to {}{}",
+        from.color_project(&config.colors),
+        to.color_project(&config.colors),
+        with_sub_projects(&tree)
+    );
+    Ok(())
+}
+
+// moves the slices and tags of `from_project` to `into_project` and deletes it
+fn merge_project(
+    conn: &Connection,
+    from_project: &db::Project,
+    into_project: &db::Project,
+) -> Result<usize, Box<dyn Error>> {
+    // tags are unique per project, so tags existing in both projects are merged into one
+    for tag in db::tag_list_by_project_id(conn, from_project.id)? {
+        match db::tag_get_id_by_name_and_project_id(conn, &tag.title, into_project.id)? {
+            Some(into_tag_id) => {
+                db::tag_relink_timeslices(conn, tag.id, into_tag_id)?;
+                db::tag_delete(conn, tag.id)?;
+            }
+            None => {
+                db::tag_move_to_project(conn, tag.id, into_project.id)?;
+            }
+        }
+    }
+    let moved = db::timeslice_move_to_project(conn, from_project.id, into_project.id)?;
+    db::project_delete(conn, from_project.id)?;
+    Ok(moved)
+}
+
+/// Merges a project into another one, its sub-projects are merged into the sub-projects of the
+/// same name there or become sub-projects of it.
+pub fn merge_command(
+    conn: &mut Connection,
+    from: &str,
+    into: &str,
+    config: &Config,
+) -> Result<(), Box<dyn Error>> {
+    let tx = conn.transaction()?;
+    let tree = get_project_tree(&tx, from)?;
+    get_project_or_parent(&tx, into)?;
+    if tree.iter().any(|project| project.title == into) {
+        return Err("cannot merge a project into itself or one of its sub-projects".into());
+    }
+    for project in &tree {
+        billing::ensure_project_unlocked(&tx, project)?;
+    }
+
+    let mut moved = 0;
+    for project in &tree {
+        let title = moved_title(&project.title, from, into);
+        match db::project_get_by_name(&tx, &title)? {
+            Some(into_project) => moved += merge_project(&tx, project, &into_project)?,
+            None => {
+                db::project_rename(&tx, project.id, &title)?;
+            }
+        }
+    }
+    tx.commit()?;
+
+    println!(
+        "merged project {} into {}{}, moved {} slices",
+        from.color_project(&config.colors),
+        into.color_project(&config.colors),
+        with_sub_projects(&tree),
+        moved
+    );
+    Ok(())
+}
+
+/// Archives or unarchives a project together with its sub-projects.
+pub fn archive_command(
+    conn: &mut Connection,
+    name: &str,
+    archived: bool,
+    config: &Config,
+) -> Result<(), Box<dyn Error>> {
+    let tx = conn.transaction()?;
+    let tree = get_project_tree(&tx, name)?;
+    for project in &tree {
+        db::project_set_archived(&tx, project.id, archived)?;
+    }
+    tx.commit()?;
+    println!(
+        "{} project {}{}",
+        if archived { "archived" } else { "unarchived" },
+        name.color_project(&config.colors),
+        with_sub_projects(&tree)
+    );
+    Ok(())
+}
@@ -0,0 +1,116 @@
+use rusqlite::Connection;
+use std::error::Error;
+use std::result::Result;
+
+use crate::colors::Colors;
+use crate::config::Config;
+use crate::db;
+use crate::projects;
+
+/// Parses an amount such as `95` or `95.50` into cents.
+pub fn parse_amount(amount: &str) -> Result<i64, Box<dyn Error>> {
+    let invalid = || format!("invalid amount {}, use e.g. 95 or 95.50", amount);
+    let (units, cents) = match amount.split_once('.') {
+        Some((units, cents)) if !cents.is_empty() && cents.len() <= 2 => {
+            (units, format!("{:0<2}", cents))
+        }
+        Some(_) => return Err(invalid().into()),
+        None => (amount, String::from("00")),
+    };
+    if units.is_empty()
+        || !units
+            .chars()
+            .chain(cents.chars())
+            .all(|c| c.is_ascii_digit())
+    {
+        return Err(invalid().into());
+    }
+    Ok(units.parse::<i64>().map_err(|_| invalid())? * 100 + cents.parse::<i64>()?)
+}
+
+pub fn format_amount(cents: i64, currency: &str) -> String {
+    format!("{}.{:02} {}", cents / 100, cents.abs() % 100, currency)
+}
+
+fn get_tag_id(conn: &Connection, project: &db::Project, tag: &str) -> Result<i64, Box<dyn Error>> {
+    db::tag_get_id_by_name_for_project(conn, tag, Some(project.id))?
+        .ok_or_else(|| format!("tag {} not found in project {}", tag, project.title).into())
+}
+
+pub fn list_command(conn: &Connection, config: &Config) -> Result<(), Box<dyn Error>> {
+    let rates = db::rate_list(conn)?;
+    if rates.is_empty() {
+        println!("No rates set. Use `punch rates set <project> <rate> <currency>` to add one.");
+    }
+    for rate in rates {
+        let title = match &rate.tag_title {
+            Some(tag) => format!("{} ({})", rate.project_title, tag),
+            None => rate.project_title.to_string(),
+        };
+        println!(
+            "{title} {rate:>14}/h",
+            title = format!("{:<28}", title)
+                .as_str()
+                .color_project(&config.colors),
+            rate = format_amount(rate.hourly_rate, &rate.currency)
+        );
+    }
+    Ok(())
+}
+
+pub fn set_command(
+    conn: &mut Connection,
+    project_name: &str,
+    tag: Option<&str>,
+    rate: &str,
+    currency: &str,
+    config: &Config,
+) -> Result<(), Box<dyn Error>> {
+    let hourly_rate = parse_amount(rate)?;
+    let currency = currency.to_uppercase();
+    let tx = conn.transaction()?;
+    let project = projects::get_project_or_parent(&tx, project_name)?;
+    match tag {
+        Some(tag) => {
+            let tag_id = get_tag_id(&tx, &project, tag)?;
+            db::tag_rate_set(&tx, project.id, tag_id, hourly_rate, &currency)?;
+        }
+        None => {
+            db::project_rate_set(&tx, project.id, hourly_rate, &currency)?;
+        }
+    }
+    tx.commit()?;
+    println!(
+        "rate of {}{} set to {}/h",
+        project_name.color_project(&config.colors),
+        tag.map(|t| format!(" ({})", t.color_tag(&config.colors)))
+            .unwrap_or_default(),
+        format_amount(hourly_rate, &currency)
+    );
+    Ok(())
+}
+
+pub fn unset_command(
+    conn: &mut Connection,
+    project_name: &str,
+    tag: Option<&str>,
+    config: &Config,
+) -> Result<(), Box<dyn Error>> {
+    let tx = conn.transaction()?;
+    let project = projects::get_project(&tx, project_name)?;
+    let deleted = match tag {
+        Some(tag) => db::tag_rate_delete(&tx, project.id, get_tag_id(&tx, &project, tag)?)?,
+        None => db::project_rate_delete(&tx, project.id)?,
+    };
+    tx.commit()?;
+    if deleted == 0 {
+        return Err("no rate set".into());
+    }
+    println!(
+        "removed rate of {}{}",
+        project_name.color_project(&config.colors),
+        tag.map(|t| format!(" ({})", t.color_tag(&config.colors)))
+            .unwrap_or_default()
+    );
+    Ok(())
+}
@@ -1,5 +1,6 @@
 use crate::migration;
 use rusqlite::{Connection, Result};
+use std::error::Error;
 
 fn migration_1_initial_structure(conn: &Connection) -> Result<bool> {
     conn.execute_batch(
@@ -31,6 +32,17 @@ fn migration_1_initial_structure(conn: &Connection) -> Result<bool> {
     Ok(true)
 }
 
+fn migration_1_down(conn: &Connection) -> Result<bool> {
+    conn.execute_batch(
+        "
+        DROP TABLE timeslice;
+        DROP TABLE tag;
+        DROP TABLE project;
+        ",
+    )?;
+    Ok(true)
+}
+
 fn migration_2_project_tags(conn: &Connection) -> Result<bool> {
     conn.execute_batch(
         "
@@ -45,19 +57,453 @@ fn migration_2_project_tags(conn: &Connection) -> Result<bool> {
     Ok(true)
 }
 
-pub fn migrate(conn: &mut Connection) -> Result<()> {
-    migration::execute_migrations(
-        conn,
-        vec![
-            migration::Migration {
-                id: 1,
-                migration_fn: migration_1_initial_structure,
-            },
-            migration::Migration {
-                id: 2,
-                migration_fn: migration_2_project_tags,
-            },
-        ],
+fn migration_2_down(conn: &Connection) -> Result<bool> {
+    conn.execute_batch("DROP TABLE timeslice_tag;")?;
+    Ok(true)
+}
+
+fn migration_3_timeslice_utc_offset(conn: &Connection) -> Result<bool> {
+    conn.execute_batch("ALTER TABLE timeslice ADD COLUMN utc_offset INTEGER;")?;
+    Ok(true)
+}
+
+fn migration_3_down(conn: &Connection) -> Result<bool> {
+    conn.execute_batch(
+        "
+        CREATE TABLE timeslice_down (
+            timeslice_id INTEGER PRIMARY KEY NOT NULL,
+            project_id INTEGER NOT NULL,
+            started_on DATETIME NOT NULL,
+            stopped_on DATETIME
+        );
+        INSERT INTO timeslice_down SELECT timeslice_id, project_id, started_on, stopped_on FROM timeslice;
+        DROP TABLE timeslice;
+        ALTER TABLE timeslice_down RENAME TO timeslice;
+        ",
+    )?;
+    Ok(true)
+}
+
+fn migration_4_project_archived(conn: &Connection) -> Result<bool> {
+    conn.execute_batch("ALTER TABLE project ADD COLUMN archived INTEGER NOT NULL DEFAULT 0;")?;
+    Ok(true)
+}
+
+fn migration_4_down(conn: &Connection) -> Result<bool> {
+    conn.execute_batch(
+        "
+        CREATE TABLE project_down (
+            project_id INTEGER PRIMARY KEY NOT NULL,
+            title TEXT NOT NULL,
+            CONSTRAINT name_unique UNIQUE (title)
+        );
+        INSERT INTO project_down SELECT project_id, title FROM project;
+        DROP TABLE project;
+        ALTER TABLE project_down RENAME TO project;
+        CREATE INDEX project_title ON project (title);
+        ",
+    )?;
+    Ok(true)
+}
+
+fn migration_5_global_tags(conn: &Connection) -> Result<bool> {
+    conn.execute_batch(
+        "
+        CREATE TABLE tag_new (
+            tag_id INTEGER PRIMARY KEY NOT NULL,
+            title TEXT NOT NULL,
+            project_id INTEGER,
+            FOREIGN KEY (project_id) REFERENCES project(project_id)
+        );
+        INSERT INTO tag_new SELECT tag_id, title, project_id FROM tag;
+        DROP TABLE tag;
+        ALTER TABLE tag_new RENAME TO tag;
+        CREATE UNIQUE INDEX tag_project_unique ON tag (title, project_id);
+        CREATE UNIQUE INDEX tag_global_unique ON tag (title) WHERE project_id IS NULL;
+        ",
+    )?;
+    Ok(true)
+}
+
+// global tags are turned into a tag of every project they are used in
+fn migration_5_down(conn: &Connection) -> Result<bool> {
+    conn.execute_batch(
+        "
+        CREATE TABLE tag_down (
+            tag_id INTEGER PRIMARY KEY NOT NULL,
+            title TEXT NOT NULL,
+            project_id INTEGER NOT NULL,
+            FOREIGN KEY (project_id) REFERENCES project(project_id)
+        );
+        CREATE UNIQUE INDEX tag_down_unique ON tag_down (title, project_id);
+        INSERT INTO tag_down SELECT tag_id, title, project_id FROM tag WHERE project_id IS NOT NULL;
+        INSERT OR IGNORE INTO tag_down (title, project_id)
+            SELECT DISTINCT tag.title, timeslice.project_id
+            FROM tag
+            JOIN timeslice_tag USING(tag_id)
+            JOIN timeslice USING(timeslice_id)
+            WHERE tag.project_id IS NULL;
+        UPDATE timeslice_tag SET tag_id = (
+            SELECT tag_down.tag_id
+            FROM tag_down
+            JOIN timeslice ON timeslice.project_id = tag_down.project_id
+            JOIN tag ON tag.title = tag_down.title
+            WHERE
+                timeslice.timeslice_id = timeslice_tag.timeslice_id
+                AND tag.tag_id = timeslice_tag.tag_id
+        )
+        WHERE tag_id IN (SELECT tag_id FROM tag WHERE project_id IS NULL);
+        DELETE FROM timeslice_tag WHERE rowid NOT IN (
+            SELECT min(rowid) FROM timeslice_tag GROUP BY timeslice_id, tag_id
+        );
+        DROP TABLE tag;
+        ALTER TABLE tag_down RENAME TO tag;
+        DROP INDEX tag_down_unique;
+        CREATE UNIQUE INDEX tag_project_unique ON tag (title, project_id);
+        ",
+    )?;
+    Ok(true)
+}
+
+fn migration_6_timeslice_note(conn: &Connection) -> Result<bool> {
+    conn.execute_batch("ALTER TABLE timeslice ADD COLUMN note TEXT;")?;
+    Ok(true)
+}
+
+fn migration_6_down(conn: &Connection) -> Result<bool> {
+    conn.execute_batch(
+        "
+        CREATE TABLE timeslice_down (
+            timeslice_id INTEGER PRIMARY KEY NOT NULL,
+            project_id INTEGER NOT NULL,
+            started_on DATETIME NOT NULL,
+            stopped_on DATETIME,
+            utc_offset INTEGER
+        );
+        INSERT INTO timeslice_down
+            SELECT timeslice_id, project_id, started_on, stopped_on, utc_offset FROM timeslice;
+        DROP TABLE timeslice;
+        ALTER TABLE timeslice_down RENAME TO timeslice;
+        ",
+    )?;
+    Ok(true)
+}
+
+// rebuilds the search rows of the slices matching `condition`
+fn search_index_sql(condition: &str) -> String {
+    format!(
+        "
+        DELETE FROM timeslice_search WHERE rowid IN (
+            SELECT timeslice_id FROM timeslice WHERE {condition}
+        );
+        INSERT INTO timeslice_search (rowid, note, project, tags)
+            SELECT
+                timeslice_id,
+                COALESCE(note, ''),
+                (SELECT title FROM project WHERE project_id = timeslice.project_id),
+                COALESCE((
+                    SELECT GROUP_CONCAT(tag.title, ' ')
+                    FROM timeslice_tag
+                    JOIN tag USING(tag_id)
+                    WHERE timeslice_tag.timeslice_id = timeslice.timeslice_id
+                ), '')
+            FROM timeslice
+            WHERE {condition};
+        ",
+        condition = condition
+    )
+}
+
+// triggers rebuilding the search row of every slice affected by a change to its note, project or
+// tags. they reference `timeslice` and have to be dropped while it is rebuilt.
+fn search_triggers_sql() -> String {
+    format!(
+        "
+        CREATE TRIGGER timeslice_search_insert AFTER INSERT ON timeslice BEGIN
+            {index_new}
+        END;
+        CREATE TRIGGER timeslice_search_update AFTER UPDATE OF project_id, note ON timeslice BEGIN
+            {index_new}
+        END;
+        CREATE TRIGGER timeslice_search_delete AFTER DELETE ON timeslice BEGIN
+            DELETE FROM timeslice_search WHERE rowid = old.timeslice_id;
+        END;
+
+        CREATE TRIGGER timeslice_search_tag_insert AFTER INSERT ON timeslice_tag BEGIN
+            {index_new}
+        END;
+        CREATE TRIGGER timeslice_search_tag_update AFTER UPDATE ON timeslice_tag BEGIN
+            {index_old}
+            {index_new}
+        END;
+        CREATE TRIGGER timeslice_search_tag_delete AFTER DELETE ON timeslice_tag BEGIN
+            {index_old}
+        END;
+
+        CREATE TRIGGER timeslice_search_project_update AFTER UPDATE OF title ON project BEGIN
+            {index_project}
+        END;
+        CREATE TRIGGER timeslice_search_tag_title_update AFTER UPDATE OF title ON tag BEGIN
+            {index_tag}
+        END;
+        ",
+        index_new = search_index_sql("timeslice_id = new.timeslice_id"),
+        index_old = search_index_sql("timeslice_id = old.timeslice_id"),
+        index_project = search_index_sql("project_id = new.project_id"),
+        index_tag = search_index_sql(
+            "timeslice_id IN (SELECT timeslice_id FROM timeslice_tag WHERE tag_id = new.tag_id)"
+        ),
+    )
+}
+
+const DROP_SEARCH_TRIGGERS_SQL: &str = "
+    DROP TRIGGER timeslice_search_insert;
+    DROP TRIGGER timeslice_search_update;
+    DROP TRIGGER timeslice_search_delete;
+    DROP TRIGGER timeslice_search_tag_insert;
+    DROP TRIGGER timeslice_search_tag_update;
+    DROP TRIGGER timeslice_search_tag_delete;
+    DROP TRIGGER timeslice_search_project_update;
+    DROP TRIGGER timeslice_search_tag_title_update;
+";
+
+// one row per timeslice, the rowid is the timeslice_id
+fn migration_7_search(conn: &Connection) -> Result<bool> {
+    conn.execute_batch(&format!(
+        "
+        CREATE VIRTUAL TABLE timeslice_search USING fts5(note, project, tags);
+        {index_all}
+        {triggers}
+        ",
+        index_all = search_index_sql("1"),
+        triggers = search_triggers_sql(),
+    ))?;
+    Ok(true)
+}
+
+fn migration_7_down(conn: &Connection) -> Result<bool> {
+    conn.execute_batch(&format!(
+        "
+        {drop_triggers}
+        DROP TABLE timeslice_search;
+        ",
+        drop_triggers = DROP_SEARCH_TRIGGERS_SQL
+    ))?;
+    Ok(true)
+}
+
+// rates are stored in cents per hour
+fn migration_8_rates(conn: &Connection) -> Result<bool> {
+    conn.execute_batch(
+        "
+        CREATE TABLE project_rate (
+            project_id INTEGER PRIMARY KEY NOT NULL,
+            hourly_rate INTEGER NOT NULL,
+            currency TEXT NOT NULL,
+            FOREIGN KEY (project_id) REFERENCES project(project_id) ON DELETE CASCADE
+        );
+        CREATE TABLE tag_rate (
+            project_id INTEGER NOT NULL,
+            tag_id INTEGER NOT NULL,
+            hourly_rate INTEGER NOT NULL,
+            currency TEXT NOT NULL,
+            PRIMARY KEY (project_id, tag_id),
+            FOREIGN KEY (project_id) REFERENCES project(project_id) ON DELETE CASCADE,
+            FOREIGN KEY (tag_id) REFERENCES tag(tag_id) ON DELETE CASCADE
+        );
+        ",
+    )?;
+    Ok(true)
+}
+
+fn migration_8_down(conn: &Connection) -> Result<bool> {
+    conn.execute_batch(
+        "
+        DROP TABLE tag_rate;
+        DROP TABLE project_rate;
+        ",
+    )?;
+    Ok(true)
+}
+
+fn migration_9_billing(conn: &Connection) -> Result<bool> {
+    conn.execute_batch(
+        "
+        ALTER TABLE project ADD COLUMN billable INTEGER NOT NULL DEFAULT 1;
+        ALTER TABLE timeslice ADD COLUMN billable INTEGER NOT NULL DEFAULT 1;
+        ALTER TABLE timeslice ADD COLUMN billed_on DATETIME;
+        ALTER TABLE timeslice ADD COLUMN invoice_ref TEXT;
+        ",
     )?;
-    Ok(())
+    Ok(true)
+}
+
+fn migration_9_down(conn: &Connection) -> Result<bool> {
+    conn.execute_batch(&format!(
+        "
+        {drop_triggers}
+        CREATE TABLE timeslice_down (
+            timeslice_id INTEGER PRIMARY KEY NOT NULL,
+            project_id INTEGER NOT NULL,
+            started_on DATETIME NOT NULL,
+            stopped_on DATETIME,
+            utc_offset INTEGER,
+            note TEXT
+        );
+        INSERT INTO timeslice_down
+            SELECT timeslice_id, project_id, started_on, stopped_on, utc_offset, note
+            FROM timeslice;
+        DROP TABLE timeslice;
+        ALTER TABLE timeslice_down RENAME TO timeslice;
+
+        CREATE TABLE project_down (
+            project_id INTEGER PRIMARY KEY NOT NULL,
+            title TEXT NOT NULL,
+            archived INTEGER NOT NULL DEFAULT 0,
+            CONSTRAINT name_unique UNIQUE (title)
+        );
+        INSERT INTO project_down SELECT project_id, title, archived FROM project;
+        DROP TABLE project;
+        ALTER TABLE project_down RENAME TO project;
+        CREATE INDEX project_title ON project (title);
+        {triggers}
+        ",
+        drop_triggers = DROP_SEARCH_TRIGGERS_SQL,
+        triggers = search_triggers_sql(),
+    ))?;
+    Ok(true)
+}
+
+// goals apply from `since` on, a goal without a project covers all projects
+fn migration_10_goals(conn: &Connection) -> Result<bool> {
+    conn.execute_batch(
+        "
+        CREATE TABLE goal (
+            goal_id INTEGER PRIMARY KEY NOT NULL,
+            project_id INTEGER,
+            period TEXT NOT NULL CHECK (period IN ('day', 'week')),
+            minutes INTEGER NOT NULL CHECK (minutes > 0),
+            since DATE NOT NULL,
+            FOREIGN KEY (project_id) REFERENCES project(project_id) ON DELETE CASCADE
+        );
+        CREATE UNIQUE INDEX goal_project_unique ON goal (project_id, period);
+        CREATE UNIQUE INDEX goal_overall_unique ON goal (period) WHERE project_id IS NULL;
+        ",
+    )?;
+    Ok(true)
+}
+
+fn migration_10_down(conn: &Connection) -> Result<bool> {
+    conn.execute_batch("DROP TABLE goal;")?;
+    Ok(true)
+}
+
+// a budget is either a number of hours or an amount of money in one currency
+fn migration_11_project_budgets(conn: &Connection) -> Result<bool> {
+    conn.execute_batch(
+        "
+        CREATE TABLE project_budget (
+            project_id INTEGER PRIMARY KEY NOT NULL,
+            minutes INTEGER CHECK (minutes > 0),
+            amount INTEGER CHECK (amount > 0),
+            currency TEXT,
+            CHECK ((minutes IS NULL) != (amount IS NULL)),
+            CHECK ((amount IS NULL) = (currency IS NULL)),
+            FOREIGN KEY (project_id) REFERENCES project(project_id) ON DELETE CASCADE
+        );
+        ",
+    )?;
+    Ok(true)
+}
+
+fn migration_11_down(conn: &Connection) -> Result<bool> {
+    conn.execute_batch("DROP TABLE project_budget;")?;
+    Ok(true)
+}
+
+// days off such as holidays and vacations, on which no working time is expected
+fn migration_12_absences(conn: &Connection) -> Result<bool> {
+    conn.execute_batch(
+        "
+        CREATE TABLE absence (
+            day DATE PRIMARY KEY NOT NULL,
+            reason TEXT
+        );
+        ",
+    )?;
+    Ok(true)
+}
+
+fn migration_12_down(conn: &Connection) -> Result<bool> {
+    conn.execute_batch("DROP TABLE absence;")?;
+    Ok(true)
+}
+
+pub fn migrations() -> Vec<migration::Migration> {
+    vec![
+        migration::Migration {
+            id: 1,
+            migration_fn: migration_1_initial_structure,
+            down_fn: Some(migration_1_down),
+        },
+        migration::Migration {
+            id: 2,
+            migration_fn: migration_2_project_tags,
+            down_fn: Some(migration_2_down),
+        },
+        migration::Migration {
+            id: 3,
+            migration_fn: migration_3_timeslice_utc_offset,
+            down_fn: Some(migration_3_down),
+        },
+        migration::Migration {
+            id: 4,
+            migration_fn: migration_4_project_archived,
+            down_fn: Some(migration_4_down),
+        },
+        migration::Migration {
+            id: 5,
+            migration_fn: migration_5_global_tags,
+            down_fn: Some(migration_5_down),
+        },
+        migration::Migration {
+            id: 6,
+            migration_fn: migration_6_timeslice_note,
+            down_fn: Some(migration_6_down),
+        },
+        migration::Migration {
+            id: 7,
+            migration_fn: migration_7_search,
+            down_fn: Some(migration_7_down),
+        },
+        migration::Migration {
+            id: 8,
+            migration_fn: migration_8_rates,
+            down_fn: Some(migration_8_down),
+        },
+        migration::Migration {
+            id: 9,
+            migration_fn: migration_9_billing,
+            down_fn: Some(migration_9_down),
+        },
+        migration::Migration {
+            id: 10,
+            migration_fn: migration_10_goals,
+            down_fn: Some(migration_10_down),
+        },
+        migration::Migration {
+            id: 11,
+            migration_fn: migration_11_project_budgets,
+            down_fn: Some(migration_11_down),
+        },
+        migration::Migration {
+            id: 12,
+            migration_fn: migration_12_absences,
+            down_fn: Some(migration_12_down),
+        },
+    ]
+}
+
+pub fn migrate(conn: &mut Connection) -> Result<(), Box<dyn Error>> {
+    migration::execute_migrations(conn, migrations())
 }
@@ -0,0 +1,44 @@
+use rusqlite::Connection;
+use std::collections::HashMap;
+use std::error::Error;
+use std::result::Result;
+
+use crate::config::Config;
+use crate::datetime::DisplayTimezone;
+use crate::log;
+
+/// Prints the slices whose note, project or tags match `query`, which uses the SQLite FTS5 query
+/// syntax, e.g. `invoice AND bug` or `project:acme`.
+pub fn search_command(
+    conn: &mut Connection,
+    query: &str,
+    split: bool,
+    tz: DisplayTimezone,
+    config: &Config,
+) -> Result<(), Box<dyn Error>> {
+    let slices = log::get_log_timeslices(
+        conn,
+        "timeslice_id IN (
+            SELECT rowid FROM timeslice_search WHERE timeslice_search MATCH :query
+        )",
+        &[(":query", &query)],
+        tz,
+    )
+    .map_err(|e| format!("invalid search query {}: {}", query, e))?;
+    if slices.is_empty() {
+        println!("No matching slices found.");
+        return Ok(());
+    }
+    log::print_log(
+        slices,
+        &log::LogOptions {
+            split,
+            breaks: false,
+            check_breaks: false,
+            by_project: false,
+        },
+        &HashMap::new(),
+        vec![],
+        config,
+    )
+}
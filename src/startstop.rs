@@ -3,15 +3,17 @@ use rusqlite::{params, Connection, OptionalExtension, NO_PARAMS};
 use std::error::Error;
 use std::result::Result;
 
+use crate::budgets;
 use crate::colors::Colors;
+use crate::config::Config;
 use crate::datetime;
 use crate::db;
 
 #[derive(Debug)]
-struct RunningTimeslice {
-    id: i64,
-    started_on: DateTime<Local>,
-    project_name: String,
+pub struct RunningTimeslice {
+    pub id: i64,
+    pub started_on: DateTime<Local>,
+    pub project_name: String,
 }
 
 impl RunningTimeslice {
@@ -24,15 +26,15 @@ impl RunningTimeslice {
     }
 }
 
-fn get_running_slice(conn: &Connection) -> Result<Option<RunningTimeslice>, Box<dyn Error>> {
+pub fn get_running_slice(conn: &Connection) -> Result<Option<RunningTimeslice>, Box<dyn Error>> {
     match conn
         .query_row::<RunningTimeslice, _, _>(
             "SELECT t.timeslice_id, t.started_on, p.title FROM timeslice t JOIN project p USING(project_id) WHERE t.stopped_on IS NULL",
             NO_PARAMS,
             |row| Ok(RunningTimeslice::new(
                  row.get(0)?,
-                 &*row.get::<_, String>(1)?,
-                 &*row.get::<_, String>(2)?))
+                 &row.get::<_, String>(1)?,
+                 &row.get::<_, String>(2)?))
         )
         .optional()? {
         Some(slice) => Ok(Some(slice)),
@@ -44,13 +46,24 @@ pub fn start_command(
     conn: &mut Connection,
     project_name: &str,
     tags: &Vec<&str>,
+    note: Option<&str>,
+    billable: Option<bool>,
+    config: &Config,
 ) -> Result<(), Box<dyn Error>> {
     match get_running_slice(conn)? {
         None => {
             let tx = conn.transaction()?;
-            let project_id = match db::project_get_by_name(&tx, project_name)? {
-                Some(project) => project.id,
-                None => db::project_create(&tx, project_name)?,
+            let (project_id, project_billable) = match db::project_get_by_name(&tx, project_name)? {
+                Some(project) if project.archived => {
+                    db::project_set_archived(&tx, project.id, false)?;
+                    println!(
+                        "project {} is no longer archived",
+                        project_name.color_project(&config.colors)
+                    );
+                    (project.id, project.billable)
+                }
+                Some(project) => (project.id, project.billable),
+                None => (db::project_create(&tx, project_name)?, true),
             };
 
             let timeslice_id = db::timeslice_create(
@@ -60,14 +73,17 @@ pub fn start_command(
                     project_id,
                     started_on: Utc::now(),
                     stopped_on: None,
+                    utc_offset: Some(datetime::local_utc_offset()),
+                    note: note.map(String::from),
+                    billable: billable.unwrap_or(project_billable),
                 },
             )?;
 
-            for tag in tags.into_iter() {
+            for tag in tags.iter() {
                 let tag_id = db::tag_get_id_or_create(
                     &tx,
                     db::TagCreate {
-                        project_id,
+                        project_id: Some(project_id),
                         title: tag.to_string(),
                     },
                 )?;
@@ -82,27 +98,33 @@ pub fn start_command(
             }
             println!(
                 "started project {} with tags {}",
-                project_name.color_project(),
-                tags.join(" ").color_tag()
+                project_name.color_project(&config.colors),
+                tags.join(" ").color_tag(&config.colors)
             );
             tx.commit()?;
+            budgets::warn_thresholds(conn, project_name, config)?;
         }
         Some(slice) => println!(
             "Slice already running for project {} started on {}",
             slice.project_name,
-            datetime::datetime_as_time_string(&slice.started_on)
+            datetime::datetime_as_time_string(&slice.started_on, config)
         ),
     };
     Ok(())
 }
 
-pub fn stop_command(conn: &mut Connection) -> Result<(), Box<dyn Error>> {
+pub fn stop_command(conn: &mut Connection, note: Option<&str>) -> Result<(), Box<dyn Error>> {
     match get_running_slice(conn)? {
         Some(slice) => {
-            conn.execute(
+            let tx = conn.transaction()?;
+            tx.execute(
                 "UPDATE timeslice SET stopped_on = ?1 WHERE timeslice_id = ?2",
                 params![Utc::now(), slice.id],
             )?;
+            if let Some(note) = note {
+                db::timeslice_append_note(&tx, slice.id, note)?;
+            }
+            tx.commit()?;
         }
         None => println!("No running slice found."),
     };
@@ -0,0 +1,44 @@
+use chrono::Local;
+use rusqlite::Connection;
+use std::error::Error;
+use std::result::Result;
+
+use crate::budgets;
+use crate::colors::Colors;
+use crate::config::Config;
+use crate::datetime;
+use crate::startstop;
+
+/*
+# example output:
+
+acme/website running since 09:12:00      1h 20m  0s  #42
+
+Budgets
+
+acme                 [████████████████░░░░]     96h  0m  0s / 120h  0m  0s      80%
+                                                24h  0m  0s remaining
+
+*/
+
+pub fn status_command(conn: &Connection, config: &Config) -> Result<(), Box<dyn Error>> {
+    match startstop::get_running_slice(conn)? {
+        Some(slice) => println!(
+            "{project} running since {started_on} {duration:>14}  {id}",
+            project = slice.project_name.color_project(&config.colors),
+            started_on = datetime::datetime_as_time_string(&slice.started_on, config)
+                .color_time(&config.colors),
+            duration = datetime::format_duration(&(Local::now() - slice.started_on), config)?
+                .color_duration(&config.colors),
+            id = format!("#{}", slice.id).color_id(&config.colors)
+        ),
+        None => println!("No running slice."),
+    }
+
+    let usages = budgets::get_usages(conn, None)?;
+    if !usages.is_empty() {
+        println!("\n{}\n", "Budgets".color_heading(&config.colors));
+        budgets::print_usages(&usages, config)?;
+    }
+    Ok(())
+}
@@ -1,84 +1,441 @@
-use chrono::{Duration, NaiveDate};
+use chrono::{DateTime, Datelike, Duration, FixedOffset, NaiveDate, Utc};
+use colored::ColoredString;
 use itertools::Itertools;
-use rusqlite::{named_params, types::Value, Connection, NO_PARAMS};
+use rusqlite::{named_params, Connection};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
 use std::error::Error;
-use std::rc::Rc;
 use std::result::Result;
+use std::str::FromStr;
 
+use crate::budgets;
 use crate::colors::Colors;
+use crate::config::{ColorTheme, Config};
 use crate::datetime;
+use crate::datetime::{DisplayTimezone, RoundedSum, Rounding};
+use crate::filter;
+use crate::filter::Filter;
+use crate::projects;
 
 pub enum GroupingMode {
     Day,
+    Week,
+    Month,
     All,
 }
 
-#[derive(Debug, PartialEq, Eq, Copy, Clone)]
-enum PeriodSummaryGrouping {
+impl FromStr for GroupingMode {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "day" => Ok(GroupingMode::Day),
+            "week" => Ok(GroupingMode::Week),
+            "month" => Ok(GroupingMode::Month),
+            "all" => Ok(GroupingMode::All),
+            _ => Err(format!("unknown grouping mode: {}", name)),
+        }
+    }
+}
+
+impl GroupingMode {
+    pub fn grouping_for(
+        &self,
+        dt: &DateTime<FixedOffset>,
+        config: &Config,
+    ) -> PeriodSummaryGrouping {
+        self.grouping_for_date(dt.naive_local().date(), config)
+    }
+
+    pub fn grouping_for_date(&self, day: NaiveDate, config: &Config) -> PeriodSummaryGrouping {
+        match self {
+            GroupingMode::Day => PeriodSummaryGrouping::Day(day),
+            GroupingMode::Week => PeriodSummaryGrouping::Week(datetime::week_start(day, config)),
+            GroupingMode::Month => PeriodSummaryGrouping::Month(day.with_day(1).unwrap()),
+            GroupingMode::All => PeriodSummaryGrouping::All,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Clone)]
+pub enum PeriodSummaryGrouping {
     Day(NaiveDate),
+    Week(NaiveDate),
+    Month(NaiveDate),
     All,
 }
 
+/// Selects what the rows of a summary are: projects broken down by tag or tags broken down by
+/// project.
+pub enum SummaryDimension {
+    Project,
+    Tag,
+}
+
+impl FromStr for SummaryDimension {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "project" => Ok(SummaryDimension::Project),
+            "tag" => Ok(SummaryDimension::Tag),
+            _ => Err(format!("unknown summary dimension: {}", name)),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct DetailSummary {
+    title: String,
+    time: RoundedSum,
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
+enum SummaryRowKey {
+    // the path of a project, parents get their own rows
+    Project(String),
+    Tag(i64),
+}
+
+#[derive(Debug)]
+struct PeriodSummaryRow {
+    key: SummaryRowKey,
+    // keys of the rows this row is nested in, ending with its own key
+    ancestors: Vec<SummaryRowKey>,
+    grouping: PeriodSummaryGrouping,
+    time: RoundedSum,
+    non_billable_time: RoundedSum,
+    title: String,
+    details: Vec<DetailSummary>,
+    last_stopped_on: DateTime<FixedOffset>,
+    // includes the time of the running slice
+    running: bool,
+}
+
+impl PeriodSummaryRow {
+    fn add_time(
+        &mut self,
+        day: NaiveDate,
+        duration: Duration,
+        details: &[&str],
+        billable: bool,
+        rounding: Option<&Rounding>,
+    ) {
+        self.time.add(day, duration, rounding);
+        if !billable {
+            self.non_billable_time.add(day, duration, rounding);
+        }
+        for detail in details {
+            let index = match self.details.iter().position(|d| &d.title == detail) {
+                Some(index) => index,
+                None => {
+                    self.details.push(DetailSummary {
+                        title: detail.to_string(),
+                        time: RoundedSum::new(),
+                    });
+                    self.details.len() - 1
+                }
+            };
+            self.details[index].time.add(day, duration, rounding);
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct SummaryTag {
+    pub id: i64,
+    pub title: String,
+}
+
 #[derive(Debug)]
-struct TagSummary {
-    tag_title: String,
-    total_time: Duration,
+pub struct SummaryTimeslice {
+    pub id: i64,
+    pub project_title: String,
+    pub started_on: DateTime<FixedOffset>,
+    pub stopped_on: DateTime<FixedOffset>,
+    pub tags: Vec<SummaryTag>,
+    /// the running slice, which ends now for the time being
+    pub running: bool,
+    pub billable: bool,
+    /// billed slices are locked against changes
+    pub billed: bool,
+    pub invoice_ref: Option<String>,
 }
 
-fn get_tag_summary(
+impl SummaryTimeslice {
+    pub fn duration(&self) -> Duration {
+        self.stopped_on - self.started_on
+    }
+
+    /// Returns the periods and days the slice counts towards with the time spent in each. Split
+    /// slices are counted proportionally on every day they touch, otherwise a slice belongs to
+    /// the day it stopped on.
+    pub fn period_pieces(
+        &self,
+        grouping_mode: &GroupingMode,
+        split: bool,
+        config: &Config,
+    ) -> Vec<(PeriodSummaryGrouping, NaiveDate, Duration)> {
+        if split {
+            datetime::split_at_midnight(self.started_on, self.stopped_on)
+                .into_iter()
+                .map(|(from, to)| {
+                    (
+                        grouping_mode.grouping_for(&from, config),
+                        from.naive_local().date(),
+                        to - from,
+                    )
+                })
+                .collect()
+        } else {
+            vec![(
+                grouping_mode.grouping_for(&self.stopped_on, config),
+                self.stopped_on.naive_local().date(),
+                self.stopped_on - self.started_on,
+            )]
+        }
+    }
+}
+
+// tags are concatenated as `id:title` pairs
+fn parse_tags(tags: &str) -> Vec<SummaryTag> {
+    tags.split(',')
+        .filter(|tag| !tag.is_empty())
+        .filter_map(|tag| {
+            let (id, title) = tag.split_at(tag.find(':')?);
+            Some(SummaryTag {
+                id: id.parse().ok()?,
+                title: title[1..].to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Loads the slices matching `filter`, including the running slice which ends now for the time
+/// being. Slices overlapping the bounds of the filter are loaded completely, callers only counting
+/// the time within the bounds have to clip them.
+pub fn get_summary_timeslices(
     conn: &Connection,
-    slice_ids: Vec<i64>,
-) -> Result<Vec<TagSummary>, Box<dyn Error>> {
-    let mut stmt = conn.prepare(
+    filter: &Filter,
+    tz: DisplayTimezone,
+) -> Result<Vec<SummaryTimeslice>, Box<dyn Error>> {
+    let mut stmt = conn.prepare(&format!(
         "
         SELECT
-            tag.title,
-            CAST(
-                total((strftime('%J',stopped_on)-strftime('%J',started_on))*24*3600)
-                AS INTEGER
-            )  AS duration,
-            group_concat(timeslice_id)
-        FROM tag
-        LEFT JOIN timeslice_tag USING(tag_id)
-        LEFT JOIN timeslice USING(timeslice_id)
+            project.title,
+            started_on,
+            stopped_on,
+            utc_offset,
+            COALESCE(GROUP_CONCAT(tag.tag_id || ':' || tag.title), ''),
+            timeslice_id,
+            timeslice.billable,
+            billed_on IS NOT NULL,
+            invoice_ref
+        FROM timeslice
+        JOIN project USING(project_id)
+        LEFT JOIN timeslice_tag USING(timeslice_id)
+        LEFT JOIN tag USING(tag_id)
         WHERE
-            timeslice_id IN rarray(:slice_ids)
-            AND tag.title IS NOT NULL
-        GROUP BY tag.tag_id
+            ({project_condition} OR (:filter_project IS NULL AND :filter_archived))
+            AND (:filter_from IS NULL OR COALESCE(stopped_on, :now) >= :filter_from)
+            AND (:filter_to IS NULL OR started_on < :filter_to)
+        GROUP BY timeslice_id
     ",
-    )?;
+        project_condition = filter::PROJECT_CONDITION
+    ))?;
 
-    let slice_ids = Rc::new(
-        slice_ids
-            .iter()
-            .copied()
-            .map(Value::from)
-            .collect::<Vec<Value>>(),
-    );
-    let rows = stmt
+    let now = Utc::now();
+    let slices = stmt
         .query_map_named(
             named_params! {
-                ":slice_ids": slice_ids
+                ":filter_project": filter.project,
+                ":filter_archived": filter.archived,
+                ":filter_from": filter.from,
+                ":filter_to": filter.to,
+                ":now": now,
             },
             |row| {
-                Ok(TagSummary {
-                    tag_title: row.get::<_, String>(0)?,
-                    total_time: Duration::seconds(row.get(1)?),
+                let utc_offset = row.get::<_, Option<i32>>(3)?;
+                let in_tz = |value: &str| {
+                    datetime::in_display_timezone(
+                        datetime::as_utc(datetime::from_rfc3339_string(value)),
+                        utc_offset,
+                        tz,
+                    )
+                };
+                let stopped_on = row.get::<_, Option<String>>(2)?;
+                Ok(SummaryTimeslice {
+                    project_title: row.get(0)?,
+                    started_on: in_tz(&row.get::<_, String>(1)?),
+                    stopped_on: match &stopped_on {
+                        Some(stopped_on) => in_tz(stopped_on),
+                        None => datetime::in_display_timezone(now, utc_offset, tz),
+                    },
+                    running: stopped_on.is_none(),
+                    tags: parse_tags(&row.get::<_, String>(4)?),
+                    id: row.get(5)?,
+                    billable: row.get(6)?,
+                    billed: row.get(7)?,
+                    invoice_ref: row.get(8)?,
                 })
             },
         )?
         .map(|row| row.unwrap())
         .collect();
-    Ok(rows)
+    Ok(slices)
 }
 
-#[derive(Debug)]
-struct PeriodSummaryRow {
-    project_id: i64,
-    grouping: PeriodSummaryGrouping,
-    total_time: Duration,
-    project_title: String,
-    slice_ids: Vec<i64>,
+fn summarize_timeslices(
+    slices: Vec<SummaryTimeslice>,
+    grouping_mode: &GroupingMode,
+    dimension: &SummaryDimension,
+    split: bool,
+    rounding: Option<&Rounding>,
+    config: &Config,
+) -> Vec<PeriodSummaryRow> {
+    let mut rows: Vec<PeriodSummaryRow> = vec![];
+    let mut row_index: HashMap<(PeriodSummaryGrouping, SummaryRowKey), usize> = HashMap::new();
+
+    for slice in slices {
+        let pieces = slice.period_pieces(grouping_mode, split, config);
+
+        // (row ancestors, row title, detail titles) the slice counts towards. time of a project
+        // rolls up to all its parents, its tags are only shown for the project itself.
+        let targets: Vec<(Vec<SummaryRowKey>, &str, Vec<&str>)> = match dimension {
+            SummaryDimension::Project => {
+                let paths = projects::project_ancestors(&slice.project_title);
+                paths
+                    .iter()
+                    .enumerate()
+                    .map(|(depth, path)| {
+                        let title = path.rsplit(projects::PROJECT_SEPARATOR).next().unwrap();
+                        let details = if depth == paths.len() - 1 {
+                            slice.tags.iter().map(|tag| tag.title.as_str()).collect()
+                        } else {
+                            vec![]
+                        };
+                        let ancestors = paths[..=depth]
+                            .iter()
+                            .map(|p| SummaryRowKey::Project(p.to_string()))
+                            .collect();
+                        (ancestors, title, details)
+                    })
+                    .collect()
+            }
+            SummaryDimension::Tag => slice
+                .tags
+                .iter()
+                .map(|tag| {
+                    (
+                        vec![SummaryRowKey::Tag(tag.id)],
+                        tag.title.as_str(),
+                        vec![slice.project_title.as_str()],
+                    )
+                })
+                .collect(),
+        };
+
+        for (grouping, day, duration) in pieces {
+            for (ancestors, title, details) in &targets {
+                let key = ancestors.last().unwrap().clone();
+                let index = *row_index.entry((grouping, key.clone())).or_insert_with(|| {
+                    rows.push(PeriodSummaryRow {
+                        key,
+                        ancestors: ancestors.clone(),
+                        grouping,
+                        time: RoundedSum::new(),
+                        non_billable_time: RoundedSum::new(),
+                        title: title.to_string(),
+                        details: vec![],
+                        last_stopped_on: slice.stopped_on,
+                        running: false,
+                    });
+                    rows.len() - 1
+                });
+                let row = &mut rows[index];
+                row.add_time(day, duration, details, slice.billable, rounding);
+                row.last_stopped_on = row.last_stopped_on.max(slice.stopped_on);
+                row.running |= slice.running;
+            }
+        }
+    }
+
+    // most recent rows first, with nested rows following their parent
+    let last_stopped_on = rows
+        .iter()
+        .map(|row| ((row.grouping, row.key.clone()), row.last_stopped_on))
+        .collect::<HashMap<_, _>>();
+    rows.sort_by_cached_key(|row| {
+        let path = row
+            .ancestors
+            .iter()
+            .map(|key| {
+                (
+                    Reverse(last_stopped_on[&(row.grouping, key.clone())]),
+                    key.clone(),
+                )
+            })
+            .collect::<Vec<_>>();
+        (row.grouping, path)
+    });
+    rows
+}
+
+/// Sums up the time of all slices per period, without breaking it down by project or tag.
+pub fn period_totals(
+    slices: &[SummaryTimeslice],
+    grouping_mode: &GroupingMode,
+    split: bool,
+    config: &Config,
+) -> BTreeMap<PeriodSummaryGrouping, Duration> {
+    let mut totals = BTreeMap::new();
+    for slice in slices {
+        for (grouping, _, duration) in slice.period_pieces(grouping_mode, split, config) {
+            let total = totals.entry(grouping).or_insert_with(Duration::zero);
+            *total = *total + duration;
+        }
+    }
+    totals
+}
+
+/// A project row of a summary, sub-projects follow their parent with a greater depth.
+pub struct ProjectTotal {
+    /// the last part of the project path
+    pub title: String,
+    pub depth: usize,
+    pub time: Duration,
+}
+
+/// The unrounded time per period and project in the order `summarize` shows them, with the time
+/// of sub-projects rolled up into their parents.
+pub fn project_totals(
+    slices: Vec<SummaryTimeslice>,
+    grouping_mode: &GroupingMode,
+    split: bool,
+    config: &Config,
+) -> Vec<(PeriodSummaryGrouping, Vec<ProjectTotal>)> {
+    let rows = summarize_timeslices(
+        slices,
+        grouping_mode,
+        &SummaryDimension::Project,
+        split,
+        None,
+        config,
+    );
+    group_summary_rows(rows)
+        .into_iter()
+        .map(|(grouping, rows)| {
+            let totals = rows
+                .into_iter()
+                .map(|row| ProjectTotal {
+                    depth: row.ancestors.len() - 1,
+                    title: row.title,
+                    time: row.time.raw,
+                })
+                .collect();
+            (grouping, totals)
+        })
+        .collect()
 }
 
 fn group_summary_rows(
@@ -91,91 +448,146 @@ fn group_summary_rows(
         .collect()
 }
 
-fn parse_group_concat(from: String) -> Vec<i64> {
-    from.split(",")
-        .into_iter()
-        .map(|v| v.parse::<i64>().unwrap())
-        .collect()
+type ColorFn = fn(&str, &ColorTheme) -> ColoredString;
+
+// the rounded time, followed by the raw time if asked for
+fn format_time(
+    time: &RoundedSum,
+    rounding: Option<&Rounding>,
+    show_raw: bool,
+    config: &Config,
+) -> Result<String, Box<dyn Error>> {
+    let rounded = format!(
+        "{:>14}",
+        datetime::format_duration(&time.rounded(rounding), config)?
+    );
+    if show_raw && rounding.is_some() {
+        Ok(format!(
+            "{} {:>18}",
+            rounded.as_str().color_duration(&config.colors),
+            format!("(raw {})", datetime::format_duration(&time.raw, config)?)
+        ))
+    } else {
+        Ok(rounded.as_str().color_duration(&config.colors).to_string())
+    }
+}
+
+/// How `summarize_command` aggregates and prints the slices.
+pub struct SummarizeOptions {
+    pub grouping_mode: GroupingMode,
+    pub dimension: SummaryDimension,
+    pub split: bool,
+    pub rounding: Option<Rounding>,
+    pub show_raw: bool,
 }
 
 pub fn summarize_command(
     conn: &mut Connection,
-    grouping_mode: GroupingMode,
+    filter: &Filter,
+    options: SummarizeOptions,
+    tz: DisplayTimezone,
+    config: &Config,
 ) -> Result<(), Box<dyn Error>> {
-    let mut stmt = conn.prepare(&format!(
-        "
-        SELECT
-            timeslice.project_id,
-            strftime('{period}', stopped_on) group_period,
-            project.title,
-            CAST(
-                total((strftime('%J',stopped_on) - strftime('%J',started_on)) * 24 * 3600)
-                AS INTEGER
-            ) AS duration,
-            max(stopped_on) AS row_order,
-            GROUP_CONCAT(timeslice.timeslice_id)
-        FROM timeslice
-        JOIN project USING(project_id)
-        WHERE stopped_on IS NOT NULL
-        GROUP BY group_period, timeslice.project_id
-        ORDER BY group_period, row_order DESC
-    ",
-        period = if let GroupingMode::Day = grouping_mode {
-            "%Y-%m-%d"
-        } else {
-            "ALL"
-        }
-    ))?;
-
-    let rows = stmt
-        .query_map(NO_PARAMS, |row| {
-            let grouping = match grouping_mode {
-                GroupingMode::Day => PeriodSummaryGrouping::Day(datetime::naivedate_from_string(
-                    &*row.get::<_, String>(1)?,
-                )),
-                _ => PeriodSummaryGrouping::All,
-            };
-
-            Ok(PeriodSummaryRow {
-                project_id: row.get(0)?,
-                grouping,
-                project_title: row.get::<_, String>(2)?,
-                total_time: Duration::seconds(row.get(3)?),
-                slice_ids: parse_group_concat(row.get::<_, String>(5)?),
-            })
-        })?
-        .map(|row| row.unwrap())
-        .collect::<Vec<PeriodSummaryRow>>();
+    let SummarizeOptions {
+        grouping_mode,
+        dimension,
+        split,
+        rounding,
+        show_raw,
+    } = options;
+    let rounding = rounding.as_ref();
+    let rows = summarize_timeslices(
+        get_summary_timeslices(conn, filter, tz)?,
+        &grouping_mode,
+        &dimension,
+        split,
+        rounding,
+        config,
+    );
+    let (color_row, color_detail): (ColorFn, ColorFn) = match dimension {
+        SummaryDimension::Project => (
+            |s, theme| s.color_project(theme),
+            |s, theme| s.color_tag(theme),
+        ),
+        SummaryDimension::Tag => (
+            |s, theme| s.color_tag(theme),
+            |s, theme| s.color_project(theme),
+        ),
+    };
 
     for (grouping, rows) in group_summary_rows(rows) {
-        if let PeriodSummaryGrouping::Day(date) = grouping {
-            println!(
+        match grouping {
+            PeriodSummaryGrouping::Day(date) => println!(
                 "\n{grouping}",
-                grouping = datetime::naivedate_format(date).color_heading()
-            );
+                grouping = datetime::naivedate_format(date, config).color_heading(&config.colors)
+            ),
+            PeriodSummaryGrouping::Week(date) => println!(
+                "\n{grouping}",
+                grouping = format!("Week of {}", datetime::naivedate_format(date, config))
+                    .color_heading(&config.colors)
+            ),
+            PeriodSummaryGrouping::Month(date) => println!(
+                "\n{grouping}",
+                grouping = date
+                    .format("%B %Y")
+                    .to_string()
+                    .color_heading(&config.colors)
+            ),
+            PeriodSummaryGrouping::All => {}
         }
         for row in rows {
+            let depth = row.ancestors.len() - 1;
+            let non_billable = if row.non_billable_time.raw.is_zero() {
+                String::new()
+            } else {
+                format!(
+                    "  ({} non-billable)",
+                    datetime::format_duration(&row.non_billable_time.rounded(rounding), config)?
+                        .color_duration(&config.colors)
+                )
+            };
             println!(
-                "    {project_title:<20} {duration:>14}",
-                project_title = row.project_title.to_string().color_project(),
-                duration = datetime::duration_as_hms_string(&row.total_time)?
-                    .to_string()
-                    .color_duration()
+                "    {indent}{title} {duration}{non_billable}{running}",
+                indent = "  ".repeat(depth),
+                title = color_row(
+                    &format!(
+                        "{:<width$}",
+                        row.title,
+                        width = 20usize.saturating_sub(2 * depth)
+                    ),
+                    &config.colors
+                ),
+                duration = format_time(&row.time, rounding, show_raw, config)?,
+                non_billable = non_billable,
+                running = if row.running { "  running" } else { "" }
             );
 
-            let tag_summary = get_tag_summary(conn, row.slice_ids)?;
-            for tag in &tag_summary {
+            for detail in &row.details {
                 println!(
-                    "      {tag_title:<18} {duration:>14}",
-                    tag_title = tag.tag_title.color_tag(),
-                    duration = datetime::duration_as_hms_string(&tag.total_time)?.color_duration()
+                    "      {indent}{title} {duration}",
+                    indent = "  ".repeat(depth),
+                    title = color_detail(
+                        &format!(
+                            "{:<width$}",
+                            detail.title,
+                            width = 18usize.saturating_sub(2 * depth)
+                        ),
+                        &config.colors
+                    ),
+                    duration = format_time(&detail.time, rounding, show_raw, config)?
                 )
             }
-            if tag_summary.len() > 0 {
+            if !row.details.is_empty() {
                 println!("\n");
             }
         }
     }
 
+    let budgets = budgets::get_usages(conn, filter.project.as_deref())?;
+    if !budgets.is_empty() {
+        println!("\n{}\n", "Budgets".color_heading(&config.colors));
+        budgets::print_usages(&budgets, config)?;
+    }
+
     Ok(())
 }
@@ -0,0 +1,260 @@
+use chrono::Duration;
+use itertools::Itertools;
+use rusqlite::{params, Connection};
+use std::collections::HashSet;
+use std::error::Error;
+use std::result::Result;
+
+use crate::billing;
+use crate::colors::Colors;
+use crate::config::Config;
+use crate::datetime;
+use crate::db;
+use crate::projects;
+
+#[derive(Debug)]
+struct TagListRow {
+    project_title: Option<String>,
+    tag_title: String,
+    usage_count: i64,
+    total_time: Duration,
+}
+
+fn get_tag_id(
+    conn: &Connection,
+    project: &db::Project,
+    title: &str,
+) -> Result<i64, Box<dyn Error>> {
+    db::tag_get_id_by_name_and_project_id(conn, title, project.id)?
+        .ok_or_else(|| format!("tag {} not found in project {}", title, project.title).into())
+}
+
+// like `get_tag_id`, but falls back to a global tag like starting a slice does, also tells
+// whether the tag is global
+fn get_tag_id_or_global(
+    conn: &Connection,
+    project: &db::Project,
+    title: &str,
+) -> Result<(i64, bool), Box<dyn Error>> {
+    if let Some(tag_id) = db::tag_get_id_by_name_and_project_id(conn, title, project.id)? {
+        return Ok((tag_id, false));
+    }
+    db::tag_get_id_by_name_for_project(conn, title, None)?
+        .map(|tag_id| (tag_id, true))
+        .ok_or_else(|| {
+            format!(
+                "tag {} not found in project {} nor globally",
+                title, project.title
+            )
+            .into()
+        })
+}
+
+pub fn list_command(
+    conn: &Connection,
+    project_name: Option<&str>,
+    config: &Config,
+) -> Result<(), Box<dyn Error>> {
+    let mut stmt = conn.prepare(
+        "
+        SELECT
+            project.title,
+            tag.title,
+            count(timeslice_id),
+            CAST(
+                total((strftime('%J',stopped_on) - strftime('%J',started_on)) * 24 * 3600)
+                AS INTEGER
+            ) AS duration
+        FROM tag
+        LEFT JOIN project ON project.project_id = tag.project_id
+        LEFT JOIN timeslice_tag USING(tag_id)
+        LEFT JOIN timeslice USING(timeslice_id)
+        WHERE ?1 IS NULL OR project.title = ?1
+        GROUP BY tag.tag_id
+        ORDER BY project.title IS NOT NULL, project.title, count(timeslice_id) DESC, tag.title
+    ",
+    )?;
+
+    let rows = stmt
+        .query_map(params![project_name], |row| {
+            Ok(TagListRow {
+                project_title: row.get(0)?,
+                tag_title: row.get(1)?,
+                usage_count: row.get(2)?,
+                total_time: Duration::seconds(row.get(3)?),
+            })
+        })?
+        .map(|row| row.unwrap())
+        .collect::<Vec<TagListRow>>();
+
+    for (project_title, rows) in &rows.into_iter().group_by(|row| row.project_title.clone()) {
+        match project_title {
+            Some(title) => println!("{}", title.as_str().color_project(&config.colors)),
+            None => println!("{}", "global".color_heading(&config.colors)),
+        }
+        for row in rows {
+            println!(
+                "    {tag_title:<18} {usage_count:>6}x {duration:>14}",
+                tag_title = row.tag_title.as_str().color_tag(&config.colors),
+                usage_count = row.usage_count,
+                duration = datetime::format_duration(&row.total_time, config)?
+                    .color_duration(&config.colors)
+            );
+        }
+    }
+    Ok(())
+}
+
+pub fn rename_command(
+    conn: &mut Connection,
+    project_name: &str,
+    from: &str,
+    to: &str,
+    config: &Config,
+) -> Result<(), Box<dyn Error>> {
+    let tx = conn.transaction()?;
+    let project = projects::get_project(&tx, project_name)?;
+    let (tag_id, _) = get_tag_id_or_global(&tx, &project, from)?;
+    billing::ensure_tag_unlocked(&tx, tag_id, from)?;
+    if db::tag_get_id_by_name_for_project(&tx, to, Some(project.id))?.is_some() {
+        return Err(format!(
+            "tag {} already exists in project {}, use `punch tags merge {} {} {}` to combine them",
+            to, project_name, project_name, from, to
+        )
+        .into());
+    }
+    db::tag_rename(&tx, tag_id, to)?;
+    tx.commit()?;
+    println!(
+        "renamed tag {} to {}",
+        from.color_tag(&config.colors),
+        to.color_tag(&config.colors)
+    );
+    Ok(())
+}
+
+pub fn merge_command(
+    conn: &mut Connection,
+    project_name: &str,
+    from: &str,
+    into: &str,
+    config: &Config,
+) -> Result<(), Box<dyn Error>> {
+    let tx = conn.transaction()?;
+    let project = projects::get_project(&tx, project_name)?;
+    let (from_tag_id, from_global) = get_tag_id_or_global(&tx, &project, from)?;
+    let (into_tag_id, into_global) = get_tag_id_or_global(&tx, &project, into)?;
+    if from_tag_id == into_tag_id {
+        return Err("cannot merge a tag into itself".into());
+    }
+    if from_global && !into_global {
+        return Err(format!(
+            "cannot merge the global tag {} into a tag of project {}",
+            from, project_name
+        )
+        .into());
+    }
+    billing::ensure_tag_unlocked(&tx, from_tag_id, from)?;
+    let relinked = db::tag_relink_timeslices(&tx, from_tag_id, into_tag_id)?;
+    db::tag_delete(&tx, from_tag_id)?;
+    tx.commit()?;
+    println!(
+        "merged tag {} into {}, relinked {} slices",
+        from.color_tag(&config.colors),
+        into.color_tag(&config.colors),
+        relinked
+    );
+    Ok(())
+}
+
+/// Moves a tag to another project together with the slices of its project carrying it. The tag
+/// is merged with a tag of the same name there, the other project tags of the slices are swapped
+/// for the tags of the same name in the other project.
+pub fn move_command(
+    conn: &mut Connection,
+    title: &str,
+    from_project_name: &str,
+    to_project_name: &str,
+    config: &Config,
+) -> Result<(), Box<dyn Error>> {
+    let tx = conn.transaction()?;
+    let from_project = projects::get_project(&tx, from_project_name)?;
+    let to_project = projects::get_project(&tx, to_project_name)?;
+    if from_project.id == to_project.id {
+        return Err(format!("tag {} is already in project {}", title, to_project_name).into());
+    }
+    let tag_id = get_tag_id(&tx, &from_project, title)?;
+    billing::ensure_tag_unlocked(&tx, tag_id, title)?;
+    let timeslice_ids = db::timeslice_list_ids_by_tag_and_project(&tx, tag_id, from_project.id)?;
+
+    match db::tag_get_id_by_name_and_project_id(&tx, title, to_project.id)? {
+        Some(existing_tag_id) => {
+            db::tag_relink_timeslices(&tx, tag_id, existing_tag_id)?;
+            db::tag_delete(&tx, tag_id)?;
+        }
+        None => {
+            db::tag_move_to_project(&tx, tag_id, to_project.id)?;
+        }
+    }
+    let from_project_tags = db::tag_list_by_project_id(&tx, from_project.id)?
+        .into_iter()
+        .map(|tag| tag.id)
+        .collect::<HashSet<i64>>();
+    for timeslice_id in &timeslice_ids {
+        let mut timeslice = db::timeslice_get(&tx, *timeslice_id)?.ok_or("slice not found")?;
+        timeslice.project_id = to_project.id;
+        db::timeslice_update(&tx, &timeslice)?;
+        for tag in db::tag_list_by_timeslice_id(&tx, *timeslice_id)? {
+            if from_project_tags.contains(&tag.id) {
+                let to_tag_id = db::tag_get_id_or_create(
+                    &tx,
+                    db::TagCreate {
+                        title: tag.title,
+                        project_id: Some(to_project.id),
+                    },
+                )?;
+                db::timeslice_tag_replace(&tx, *timeslice_id, tag.id, to_tag_id)?;
+            }
+        }
+    }
+    tx.commit()?;
+    println!(
+        "moved tag {} and {} slices from {} to {}",
+        title.color_tag(&config.colors),
+        timeslice_ids.len(),
+        from_project_name.color_project(&config.colors),
+        to_project_name.color_project(&config.colors)
+    );
+    Ok(())
+}
+
+/// Makes a tag global, merging the tags of the same name of all projects into it.
+pub fn global_command(
+    conn: &mut Connection,
+    title: &str,
+    config: &Config,
+) -> Result<(), Box<dyn Error>> {
+    let tx = conn.transaction()?;
+    let global_tag_id = db::tag_get_id_or_create(
+        &tx,
+        db::TagCreate {
+            title: title.to_string(),
+            project_id: None,
+        },
+    )?;
+    let project_tags = db::tag_list_project_tags_by_name(&tx, title)?;
+    for tag in &project_tags {
+        billing::ensure_tag_unlocked(&tx, tag.id, title)?;
+    }
+    for tag in &project_tags {
+        db::tag_relink_timeslices(&tx, tag.id, global_tag_id)?;
+        db::tag_delete(&tx, tag.id)?;
+    }
+    tx.commit()?;
+    println!(
+        "tag {} is global, merged {} project tags into it",
+        title.color_tag(&config.colors),
+        project_tags.len()
+    );
+    Ok(())
+}
@@ -0,0 +1,155 @@
+use chrono::{Duration, NaiveDate, Timelike};
+use colored::Colorize;
+use itertools::Itertools;
+use rusqlite::{named_params, Connection};
+use std::collections::HashMap;
+use std::error::Error;
+use std::result::Result;
+
+use crate::colors;
+use crate::colors::Colors;
+use crate::config::Config;
+use crate::datetime;
+use crate::datetime::DisplayTimezone;
+use crate::log;
+use crate::log::LogTimeslice;
+
+/*
+# example output:
+
+                      0     3     6     9     12    15    18    21
+Mon 14 September 2020 ················█████████████████···············      8h 10m  0s
+Tue 15 September 2020 ··················██████████████████············      9h  0m  0s
+
+█ acme  █ acme/website
+
+*/
+
+const CELLS_PER_HOUR: i64 = 2;
+const CELL_SECONDS: i64 = 3600 / CELLS_PER_HOUR;
+const CELLS: i64 = 24 * CELLS_PER_HOUR;
+
+// each cell shows the project with the most time in it, `None` for cells without any time
+fn day_cells(slices: &[&LogTimeslice], projects: &[&str]) -> Vec<Option<usize>> {
+    // seconds per project index in each cell
+    let mut cells: Vec<HashMap<usize, i64>> = vec![HashMap::new(); CELLS as usize];
+    for slice in slices {
+        let project = projects
+            .iter()
+            .position(|p| *p == slice.project_name)
+            .unwrap();
+        let start = slice.started_on.time().num_seconds_from_midnight() as i64;
+        let end = start + slice.duration.num_seconds();
+        let mut cell = start / CELL_SECONDS;
+        while cell < CELLS && cell * CELL_SECONDS < end {
+            let overlap = end.min((cell + 1) * CELL_SECONDS) - start.max(cell * CELL_SECONDS);
+            *cells[cell as usize].entry(project).or_insert(0) += overlap;
+            cell += 1;
+        }
+    }
+    cells
+        .into_iter()
+        .map(|cell| {
+            cell.into_iter()
+                .max_by_key(|(project, seconds)| (*seconds, std::cmp::Reverse(*project)))
+                .map(|(project, _)| project)
+        })
+        .collect()
+}
+
+/// Prints the day of `date`, or its week, as bars of 24 hours in the layout shown above.
+pub fn timeline_command(
+    conn: &Connection,
+    date: NaiveDate,
+    week: bool,
+    tz: DisplayTimezone,
+    config: &Config,
+) -> Result<(), Box<dyn Error>> {
+    let (from, to) = if week {
+        let start = datetime::week_start(date, config);
+        (start, start + Duration::days(6))
+    } else {
+        (date, date)
+    };
+
+    // a day of margin as slices are assigned to days in the display timezone
+    let slices = log::get_log_timeslices(
+        conn,
+        "NOT project.archived
+            AND (stopped_on IS NULL OR stopped_on >= :from)
+            AND started_on < :to",
+        named_params! {
+            ":from": (from - Duration::days(1)).format(datetime::DATE_FORMAT_YMD).to_string(),
+            ":to": (to + Duration::days(2)).format(datetime::DATE_FORMAT_YMD).to_string(),
+        },
+        tz,
+    )?
+    .into_iter()
+    .flat_map(LogTimeslice::split_at_midnight)
+    .filter(|slice| from <= slice.day && slice.day <= to)
+    .collect::<Vec<_>>();
+
+    let projects = slices
+        .iter()
+        .map(|slice| slice.project_name.as_str())
+        .sorted()
+        .dedup()
+        .collect::<Vec<_>>();
+    let days = (0..=(to - from).num_days())
+        .map(|i| from + Duration::days(i))
+        .collect::<Vec<_>>();
+    let width = days
+        .iter()
+        .map(|day| datetime::naivedate_format(*day, config).chars().count())
+        .max()
+        .unwrap_or(0);
+
+    println!(
+        "{:width$} {}",
+        "",
+        (0..24)
+            .step_by(3)
+            .map(|hour| format!("{:<6}", hour))
+            .collect::<String>(),
+        width = width
+    );
+    for day in days {
+        let day_slices = slices
+            .iter()
+            .filter(|slice| slice.day == day)
+            .collect::<Vec<_>>();
+        let total = day_slices
+            .iter()
+            .fold(Duration::zero(), |sum, slice| sum + slice.duration);
+        let bar = day_cells(&day_slices, &projects)
+            .into_iter()
+            .map(|cell| match cell {
+                Some(project) => colors::color_palette("█", project).to_string(),
+                None => "·".dimmed().to_string(),
+            })
+            .collect::<String>();
+        println!(
+            "{day} {bar}  {total:>14}",
+            day = format!(
+                "{:<width$}",
+                datetime::naivedate_format(day, config),
+                width = width
+            )
+            .color_heading(&config.colors),
+            bar = bar,
+            total = datetime::format_duration(&total, config)?.color_duration(&config.colors)
+        );
+    }
+
+    if !projects.is_empty() {
+        println!(
+            "\n{}",
+            projects
+                .iter()
+                .enumerate()
+                .map(|(i, project)| format!("{} {}", colors::color_palette("█", i), project))
+                .join("  ")
+        );
+    }
+    Ok(())
+}